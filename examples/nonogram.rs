@@ -264,7 +264,7 @@ fn edit_nonogram(
         let (width, height) = nonogram.size;
         let (x, y) = (anchor.x, anchor.y);
 
-        for TileUpdateEvent { modification } in tile_update_event_reader.iter() {
+        for TileUpdateEvent { modification, .. } in tile_update_event_reader.iter() {
             match *modification {
                 TileModification::Added { old, new } => {
                     if let Some(tile_pos) = tile_pos_q.get(new).ok() {
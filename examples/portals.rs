@@ -8,7 +8,11 @@ use bevy_prototype_lyon::prelude::ShapePlugin;
 use sandbox::{
     entity::player::{DespawnPlayerCommand, Player, SpawnPlayerCommand},
     input::{CursorPos, InputPlugin},
-    phys::{movement::Control, terrain::handle_platforms, PhysPlugin},
+    phys::{
+        movement::{Control, LookDir},
+        terrain::handle_platforms,
+        PhysPlugin,
+    },
 };
 
 use bevy_xpbd_2d::{math::*, parry::bounding_volume::Aabb};
@@ -284,6 +288,17 @@ fn player_in_portal(
                 LockedAxes::new().lock_rotation(),
                 Friction::new(0.),
                 Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
+                // Needed to run the same movement systems as the player
+                ShapeCaster::new(
+                    Collider::cuboid(15.5, 15.5),
+                    Vector::NEG_Y * 0.05,
+                    0.,
+                    Vector::NEG_Y,
+                )
+                .with_ignore_origin_penetration(true)
+                .with_max_time_of_impact(0.2)
+                .with_max_hits(1),
+                LookDir::Right,
                 Control::default(),
                 CollisionLayers::new([Layer::Normal], [Layer::Normal]),
                 GravityScale(1.0),
@@ -1,5 +1,4 @@
 use bevy::prelude::*;
-use bevy_ecs_tilemap::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_pancam::PanCam;
 use bevy_pancam::PanCamPlugin;
@@ -8,16 +7,16 @@ use bevy_xpbd_2d::prelude::*;
 use sandbox::editor::render::setup_display;
 use sandbox::editor::EditorPlugin;
 use sandbox::editor::EditorState;
-use sandbox::entity::pebble::SpawnPebble;
 use sandbox::entity::player::DespawnPlayerCommand;
 use sandbox::entity::player::Player;
 use sandbox::entity::player::SpawnPlayerCommand;
+use sandbox::entity::{
+    creature::SpawnCreatureCommand,
+    holdable::{Holdable, SpawnHoldable},
+    pebble::{SpawnPebble, PEBBLE_DENSITY},
+};
 use sandbox::input::InputPlugin;
-use sandbox::level::layer::Layer;
-use sandbox::level::placement::StorageAccess;
-use sandbox::level::placement::TileProperties;
-use sandbox::level::tile::InsertTileColliderCommand;
-use sandbox::level::tile::TileKind;
+use sandbox::level::collision::InsertMapCollidersCommand;
 use sandbox::level::tpos_wpos;
 use sandbox::level::LevelPlugin;
 use sandbox::level::SpawnMapCommand;
@@ -25,6 +24,7 @@ use sandbox::level::TileCursor;
 use sandbox::lifetime::LifetimePlugin;
 use sandbox::phys::movement::LookDir;
 use sandbox::phys::PhysPlugin;
+use sandbox::replay::ReplayPlugin;
 
 fn main() {
     let mut app = App::new();
@@ -38,6 +38,7 @@ fn main() {
         PhysPlugin,
         LifetimePlugin,
         EditorPlugin,
+        ReplayPlugin,
     ));
     app.insert_resource(ClearColor(Color::DARK_GRAY))
         .insert_resource(Gravity(Vector::NEG_Y * 320.0))
@@ -68,6 +69,12 @@ fn respawn_player(mut cmds: Commands, keys: Res<Input<KeyCode>>, tile_cursor: Re
         let size = Vector::new(14., 14.);
         cmds.add(SpawnPlayerCommand::new(pos, size, ()));
     }
+    if keys.just_pressed(KeyCode::K) {
+        cmds.add(SpawnCreatureCommand {
+            pos,
+            size: Vector::new(14., 14.),
+        });
+    }
 }
 
 fn spawn_rock(mut cmds: Commands, keys: Res<Input<KeyCode>>, tile_cursor: Res<TileCursor>) {
@@ -76,12 +83,32 @@ fn spawn_rock(mut cmds: Commands, keys: Res<Input<KeyCode>>, tile_cursor: Res<Ti
     };
     let pos = tpos_wpos(&tile_cursor);
     if keys.just_pressed(KeyCode::G) {
+        // Holding shift spawns one that floats
+        let density = if keys.pressed(KeyCode::ShiftLeft) {
+            0.5
+        } else {
+            PEBBLE_DENSITY
+        };
         cmds.add(SpawnPebble {
             pos,
             vel: Vec2::ZERO,
             lifetime: Some(3.0),
+            density,
         })
     }
+    for (key, holdable) in [
+        (KeyCode::V, Holdable::spear()),
+        (KeyCode::B, Holdable::bomb()),
+    ] {
+        if keys.just_pressed(key) {
+            cmds.add(SpawnHoldable {
+                holdable,
+                pos,
+                vel: Vec2::ZERO,
+                lifetime: None,
+            });
+        }
+    }
 }
 
 fn draw_look_dir(q_player: Query<(&LookDir, &Transform), With<Player>>, mut gizmos: Gizmos) {
@@ -124,40 +151,8 @@ fn setup(mut cmds: Commands) {
     cmds.add(SpawnMapCommand::new(UVec2::new(64, 32), 16));
 }
 
-fn spawn_collisions(
-    keys: Res<Input<KeyCode>>,
-    mut cmds: Commands,
-    tiles: StorageAccess,
-    tiles_pos: Query<(&TilePos, &TileTextureIndex, &TileFlip)>,
-) {
+fn spawn_collisions(keys: Res<Input<KeyCode>>, mut cmds: Commands) {
     if keys.just_pressed(KeyCode::Q) {
-        tiles
-            .storage(Layer::World)
-            .unwrap()
-            .iter()
-            .for_each(|tile_entity| {
-                let Some(tile_entity) = tile_entity else {
-                    return;
-                };
-
-                let Ok((pos, id, flip)) = tiles_pos.get(*tile_entity) else {
-                    return;
-                };
-
-                let center = tpos_wpos(pos);
-
-                let properties = TileProperties {
-                    id: *id,
-                    flip: *flip,
-                };
-                let kind = TileKind::from(properties.id);
-
-                cmds.add(InsertTileColliderCommand {
-                    tile_entity: *tile_entity,
-                    pos: center,
-                    properties,
-                    kind,
-                });
-            });
+        cmds.add(InsertMapCollidersCommand);
     }
 }
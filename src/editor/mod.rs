@@ -9,31 +9,37 @@ use leafwing_input_manager::{prelude::*, user_input::Modifier, Actionlike, Input
 use crate::{
     file_picker,
     level::{
-        layer::ALL_LAYERS, placement::StorageAccess, serialization::LevelSerializer,
-        SpawnMapCommand,
+        layer::ALL_LAYERS,
+        placement::{Anchor, StorageAccess},
+        serialization::LevelSerializer,
+        PlayerSpawn, SpawnMapCommand, TileCursor,
     },
     util::box_lines,
 };
 use crate::{level::layer::Layer, ui::draw_confirmation_dialog};
 
 use self::{
+    overlay::{draw_grid_overlay, draw_nav_graph, draw_rulers, GridOverlay},
     palette::{load_palette_images, parse_palette_images, Palette, PaletteHandles, Palettes},
+    play::{draw_spawn, start_play_test, stop_play_test, EditorMode},
     render::{display_images, render_map_images, MapTexture},
     tiles::{load_manifests, load_tile_images, load_tiles, Manifest, Manifests, Materials, Tiles},
     tools::{
         area::{ActiveMode, AreaTool},
         erase::EraseTool,
-        paint::PaintTool,
+        paint::{PaintBrush, PaintTool},
         platform::PlatformTool,
         pole::PoleTool,
         run_tool,
         slope::SlopeTool,
         ToolId, ToolSet,
     },
-    ui::draw_ui,
+    ui::{draw_ui, minimap::update_minimap_texture},
 };
 
+pub mod overlay;
 pub mod palette;
+pub mod play;
 pub mod render;
 pub mod tiles;
 pub mod tools;
@@ -53,11 +59,15 @@ impl Plugin for EditorPlugin {
         app.register_type::<Palette>();
         app.register_type::<Palettes>();
         app.register_type::<EditorState>();
+        app.register_type::<GridOverlay>();
 
         app.add_state::<AppState>();
+        app.add_state::<EditorMode>();
         app.init_resource::<EditorState>();
         app.init_resource::<ActiveMode>();
         app.init_resource::<Manifests>();
+        app.init_resource::<GridOverlay>();
+        app.init_resource::<PaintBrush>();
 
         app.add_event::<EditorEvent>().add_event::<PickerEvent>();
 
@@ -70,6 +80,8 @@ impl Plugin for EditorPlugin {
                 Name::new("EditorActions"),
             ));
         });
+        app.add_systems(OnEnter(EditorMode::Play), start_play_test);
+        app.add_systems(OnExit(EditorMode::Play), stop_play_test);
         // Loading state
         app.add_systems(
             OnEnter(AppState::Loading),
@@ -89,6 +101,7 @@ impl Plugin for EditorPlugin {
                     apply_editor_actions,
                     render_map_images,
                     display_images.run_if(resource_exists::<MapTexture>()),
+                    update_minimap_texture,
                 )
                     .run_if(in_state(AppState::Display)),
                 (
@@ -97,13 +110,18 @@ impl Plugin for EditorPlugin {
                     handle_load,
                     handle_close,
                     handle_new,
+                    handle_resize,
                 )
                     .run_if(on_event::<EditorEvent>()),
                 draw_ui,
-                apply_tool,
+                apply_tool.run_if(in_state(EditorMode::Edit)),
+                draw_spawn,
                 draw_confirmation_dialog::<EditorEvent>,
                 handle_picker_events.run_if(on_event::<PickerEvent>()),
                 render_tilemap_outline,
+                draw_grid_overlay,
+                draw_nav_graph,
+                draw_rulers.after(draw_ui),
             ),
         );
     }
@@ -224,6 +242,7 @@ pub struct EnabledUiElements {
     pub inspector: bool,
     pub tool_panel: bool,
     pub egui_debug: bool,
+    pub minimap: bool,
 }
 
 impl Default for EnabledUiElements {
@@ -232,6 +251,7 @@ impl Default for EnabledUiElements {
             inspector: true,
             tool_panel: true,
             egui_debug: false,
+            minimap: true,
         }
     }
 }
@@ -264,6 +284,9 @@ pub enum EditorActions {
     Save,
     SaveAs,
     ReloadMapDisplay,
+    ToggleMinimap,
+    SetSpawn,
+    TogglePlay,
 }
 
 fn editor_actions_map() -> InputMap<EditorActions> {
@@ -277,6 +300,9 @@ fn editor_actions_map() -> InputMap<EditorActions> {
     input_map.insert(KeyCode::T, CycleToolMode);
     input_map.insert(KeyCode::Z, ReloadMapDisplay);
     input_map.insert(KeyCode::P, CyclePalette);
+    input_map.insert(KeyCode::M, ToggleMinimap);
+    input_map.insert(KeyCode::R, SetSpawn);
+    input_map.insert(KeyCode::F5, TogglePlay);
 
     input_map.insert_modified(Modifier::Control, MouseButton::Left, EditorActions::Area);
     input_map.insert_modified(Modifier::Shift, KeyCode::C, EditorActions::CycleLayer);
@@ -298,6 +324,10 @@ fn apply_editor_actions(
     mut event_writer: EventWriter<EditorEvent>,
     mut editor_state: ResMut<EditorState>,
     mut palettes: ResMut<Palettes>,
+    tile_cursor: Res<TileCursor>,
+    mut spawn: ResMut<PlayerSpawn>,
+    mode: Res<State<EditorMode>>,
+    mut next_mode: ResMut<NextState<EditorMode>>,
 ) {
     let Some(actions) = actions.get_single().ok() else {
         return;
@@ -306,6 +336,8 @@ fn apply_editor_actions(
     actions
         .get_just_pressed()
         .iter()
+        // Only leaving play mode is possible while play testing
+        .filter(|action| *mode.get() == EditorMode::Edit || **action == EditorActions::TogglePlay)
         .for_each(|action| match action {
             EditorActions::ApplyTool => {}
             EditorActions::Area => {}
@@ -341,6 +373,18 @@ fn apply_editor_actions(
                 event_writer.send(EditorEvent::SaveAs);
             }
             EditorActions::ReloadMapDisplay => {}
+            EditorActions::ToggleMinimap => {
+                editor_state.enabled.minimap = !editor_state.enabled.minimap;
+            }
+            EditorActions::SetSpawn => {
+                if *mode.get() == EditorMode::Edit && tile_cursor.is_some() {
+                    **spawn = **tile_cursor;
+                    editor_state.unsaved_changes = true;
+                }
+            }
+            EditorActions::TogglePlay => {
+                next_mode.set(mode.get().toggled());
+            }
         });
 }
 
@@ -351,6 +395,7 @@ pub enum EditorEvent {
     Save(PathBuf),
     SaveAs,
     Load(PathBuf),
+    Resize(UVec2, Anchor),
 }
 
 fn handle_save(
@@ -399,6 +444,7 @@ fn handle_close(
     map: Query<Entity, With<TileStorage>>,
     mut storage: StorageAccess,
     mut editor_state: ResMut<EditorState>,
+    mut spawn: ResMut<PlayerSpawn>,
 ) {
     for ev in editor_events.iter() {
         if matches!(ev, EditorEvent::Close) {
@@ -412,6 +458,7 @@ fn handle_close(
             }
             cmds.entity(entity).despawn_recursive();
             editor_state.reset_path();
+            **spawn = None;
         }
     }
 }
@@ -422,6 +469,7 @@ fn handle_new(
     map: Query<Entity, (With<TileStorage>, With<Layer>)>,
     mut storage: StorageAccess,
     mut editor_state: ResMut<EditorState>,
+    mut spawn: ResMut<PlayerSpawn>,
 ) {
     for ev in editor_events.iter() {
         if matches!(ev, EditorEvent::New) {
@@ -433,6 +481,27 @@ fn handle_new(
             }
             cmds.add(SpawnMapCommand::new(UVec2::new(64, 32), 16));
             editor_state.reset_path();
+            **spawn = None;
+        }
+    }
+}
+
+fn handle_resize(
+    mut editor_events: EventReader<EditorEvent>,
+    mut storage: StorageAccess,
+    mut editor_state: ResMut<EditorState>,
+    mut spawn: ResMut<PlayerSpawn>,
+) {
+    for ev in editor_events.iter() {
+        if let EditorEvent::Resize(size, anchor) = ev {
+            let Some((_, old_size)) = storage.transform_size(Layer::World) else {
+                continue;
+            };
+            let old_size = UVec2::new(old_size.x, old_size.y);
+            // The spawn moves along with the map and is lost when cropped
+            **spawn = spawn.and_then(|pos| anchor.shift(&pos, old_size, *size));
+            storage.resize(*size, *anchor);
+            editor_state.unsaved_changes = true;
         }
     }
 }
@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_pancam::PanCam;
+
+use crate::level::{
+    layer::Layer,
+    nav::{NavGraph, NavLink},
+    placement::StorageAccess,
+};
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct GridOverlay {
+    pub show_grid: bool,
+    // Draw a highlighted line every n tiles
+    pub major_every: u32,
+    pub show_rulers: bool,
+    pub show_rooms: bool,
+    pub show_nav: bool,
+    // Size of the game camera in tiles
+    pub room_size: UVec2,
+}
+
+impl Default for GridOverlay {
+    fn default() -> Self {
+        Self {
+            show_grid: false,
+            major_every: 8,
+            show_rulers: false,
+            show_rooms: false,
+            show_nav: false,
+            room_size: UVec2::new(80, 45),
+        }
+    }
+}
+
+pub fn draw_grid_overlay(mut gizmos: Gizmos, storage: StorageAccess, overlay: Res<GridOverlay>) {
+    if !overlay.show_grid && !overlay.show_rooms {
+        return;
+    }
+    let Some((transform, size)) = storage.transform_size(Layer::World) else {
+        return;
+    };
+
+    let min = transform.translation.truncate() - Vec2::splat(8.);
+    let max = min + Vec2::from(size) * 16.;
+
+    let minor = Color::rgba(1., 1., 1., 0.05);
+    let major = Color::rgba(1., 1., 1., 0.2);
+    let room = Color::rgba(1., 0.6, 0., 0.6);
+    let major_every = overlay.major_every.max(1);
+    let room_size = overlay.room_size.max(UVec2::ONE);
+
+    let line_color = |idx: u32, room_every: u32| -> Option<Color> {
+        if overlay.show_rooms && idx % room_every == 0 {
+            Some(room)
+        } else if overlay.show_grid && idx % major_every == 0 {
+            Some(major)
+        } else if overlay.show_grid {
+            Some(minor)
+        } else {
+            None
+        }
+    };
+
+    for x in 0..=size.x {
+        if let Some(color) = line_color(x, room_size.x) {
+            let wx = min.x + x as f32 * 16.;
+            gizmos.line_2d(Vec2::new(wx, min.y), Vec2::new(wx, max.y), color);
+        }
+    }
+    for y in 0..=size.y {
+        if let Some(color) = line_color(y, room_size.y) {
+            let wy = min.y + y as f32 * 16.;
+            gizmos.line_2d(Vec2::new(min.x, wy), Vec2::new(max.x, wy), color);
+        }
+    }
+}
+
+pub fn draw_rulers(
+    mut contexts: EguiContexts,
+    storage: StorageAccess,
+    overlay: Res<GridOverlay>,
+    camera: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+) {
+    if !overlay.show_rulers {
+        return;
+    }
+    let Some((transform, size)) = storage.transform_size(Layer::World) else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+
+    let ctx = contexts.ctx_mut();
+    // Area of the screen not covered by any panels
+    let area = ctx.available_rect();
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("rulers"),
+    ));
+
+    let thickness = 16.;
+    let background = egui::Color32::from_black_alpha(180);
+    let text_color = egui::Color32::from_gray(200);
+    let font = egui::FontId::monospace(10.);
+
+    painter.rect_filled(
+        egui::Rect::from_min_size(area.min, egui::Vec2::new(area.width(), thickness)),
+        0.,
+        background,
+    );
+    painter.rect_filled(
+        egui::Rect::from_min_size(area.min, egui::Vec2::new(thickness, area.height())),
+        0.,
+        background,
+    );
+
+    let min = transform.translation.truncate() - Vec2::splat(8.);
+    let major_every = overlay.major_every.max(1);
+    let to_viewport = |pos: Vec2| camera.world_to_viewport(camera_transform, pos.extend(0.));
+
+    for x in (0..=size.x).step_by(major_every as usize) {
+        let Some(screen) = to_viewport(Vec2::new(min.x + x as f32 * 16., min.y)) else {
+            continue;
+        };
+        if screen.x < area.left() + thickness || screen.x > area.right() {
+            continue;
+        }
+        painter.line_segment(
+            [
+                egui::Pos2::new(screen.x, area.top() + thickness / 2.),
+                egui::Pos2::new(screen.x, area.top() + thickness),
+            ],
+            egui::Stroke::new(1., text_color),
+        );
+        painter.text(
+            egui::Pos2::new(screen.x + 2., area.top()),
+            egui::Align2::LEFT_TOP,
+            x.to_string(),
+            font.clone(),
+            text_color,
+        );
+    }
+
+    for y in (0..=size.y).step_by(major_every as usize) {
+        let Some(screen) = to_viewport(Vec2::new(min.x, min.y + y as f32 * 16.)) else {
+            continue;
+        };
+        if screen.y < area.top() + thickness || screen.y > area.bottom() {
+            continue;
+        }
+        painter.line_segment(
+            [
+                egui::Pos2::new(area.left() + thickness / 2., screen.y),
+                egui::Pos2::new(area.left() + thickness, screen.y),
+            ],
+            egui::Stroke::new(1., text_color),
+        );
+        painter.text(
+            egui::Pos2::new(area.left(), screen.y - 2.),
+            egui::Align2::LEFT_BOTTOM,
+            y.to_string(),
+            font.clone(),
+            text_color,
+        );
+    }
+}
+
+pub fn draw_nav_graph(mut gizmos: Gizmos, nav: Res<NavGraph>, overlay: Res<GridOverlay>) {
+    if !overlay.show_nav {
+        return;
+    }
+
+    for node in nav.iter_nodes() {
+        let color = if nav.walkable(node) {
+            Color::GREEN
+        } else {
+            Color::YELLOW
+        };
+        gizmos.circle_2d(NavGraph::cell_center(node), 2., color);
+    }
+    for (from, edge) in nav.iter_edges() {
+        let color = match edge.link {
+            NavLink::Walk => Color::rgba(0., 1., 0., 0.5),
+            NavLink::Climb => Color::rgba(1., 1., 0., 0.5),
+            NavLink::Jump => Color::rgba(0., 0.6, 1., 0.3),
+            NavLink::Fall => Color::rgba(1., 0.3, 0.3, 0.3),
+        };
+        gizmos.line_2d(
+            NavGraph::cell_center(from),
+            NavGraph::cell_center(edge.to),
+            color,
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_xpbd_2d::math::Vector;
+
+use crate::{
+    entity::{
+        creature::CreatureAi,
+        holdable::Holdable,
+        player::{DespawnPlayerCommand, SpawnPlayerCommand},
+    },
+    level::{
+        collision::{InsertMapCollidersCommand, RemoveMapCollidersCommand},
+        layer::{Layer, ALL_LAYERS},
+        placement::{StorageAccess, TileProperties},
+        tpos_wpos,
+        trigger::TriggerZone,
+        PlayerSpawn,
+    },
+    util::box_lines,
+};
+
+use super::{tools::ToolId, EditorState};
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+pub enum EditorMode {
+    #[default]
+    Edit,
+    Play,
+}
+
+impl EditorMode {
+    pub fn toggled(&self) -> Self {
+        match self {
+            EditorMode::Edit => EditorMode::Play,
+            EditorMode::Play => EditorMode::Edit,
+        }
+    }
+}
+
+// State of the map and editor before entering play mode
+#[derive(Resource)]
+pub struct PlaySnapshot {
+    tiles: Vec<(Layer, Vec<(TilePos, TileProperties)>)>,
+    triggers: Vec<(TilePos, TriggerZone)>,
+    active_tool: ToolId,
+    current_layer: Layer,
+    unsaved_changes: bool,
+    // Creatures and holdables that were already there, anything else was spawned while playing
+    entities: HashSet<Entity>,
+}
+
+pub fn start_play_test(
+    mut cmds: Commands,
+    storage: StorageAccess,
+    editor_state: Res<EditorState>,
+    spawn: Res<PlayerSpawn>,
+    triggers: Query<(&TilePos, &TriggerZone)>,
+    entities: Query<Entity, Or<(With<CreatureAi>, With<Holdable>)>>,
+) {
+    cmds.insert_resource(PlaySnapshot {
+        tiles: ALL_LAYERS
+            .iter()
+            .map(|layer| (*layer, storage.tiles(*layer)))
+            .collect(),
+        triggers: triggers
+            .iter()
+            .map(|(pos, zone)| (*pos, zone.clone()))
+            .collect(),
+        active_tool: editor_state.active_tool,
+        current_layer: editor_state.current_layer,
+        unsaved_changes: editor_state.unsaved_changes,
+        entities: entities.iter().collect(),
+    });
+
+    cmds.add(InsertMapCollidersCommand);
+
+    let Some(spawn) = **spawn else {
+        warn!("No spawn tile set. Play testing without a player");
+        return;
+    };
+    cmds.add(DespawnPlayerCommand);
+    cmds.add(SpawnPlayerCommand::new(
+        tpos_wpos(&spawn),
+        Vector::new(14., 14.),
+        (),
+    ));
+}
+
+pub fn stop_play_test(
+    mut cmds: Commands,
+    mut storage: StorageAccess,
+    mut editor_state: ResMut<EditorState>,
+    snapshot: Option<Res<PlaySnapshot>>,
+    entities: Query<Entity, Or<(With<CreatureAi>, With<Holdable>)>>,
+) {
+    cmds.add(DespawnPlayerCommand);
+    cmds.add(RemoveMapCollidersCommand);
+
+    let Some(snapshot) = snapshot else {
+        return;
+    };
+
+    for entity in entities.iter() {
+        if !snapshot.entities.contains(&entity) {
+            cmds.entity(entity).despawn_recursive();
+        }
+    }
+
+    // Replacing every tile also gets rid of the colliders inserted for play mode
+    for (layer, tiles) in snapshot.tiles.iter() {
+        storage.clear(*layer);
+        for (pos, properties) in tiles.iter() {
+            storage.replace(pos, *properties, *layer);
+        }
+    }
+    for (pos, zone) in snapshot.triggers.iter() {
+        storage.insert(pos, Layer::World, zone.clone());
+    }
+
+    editor_state.active_tool = snapshot.active_tool;
+    editor_state.current_layer = snapshot.current_layer;
+    editor_state.unsaved_changes = snapshot.unsaved_changes;
+    cmds.remove_resource::<PlaySnapshot>();
+}
+
+pub fn draw_spawn(spawn: Res<PlayerSpawn>, mut gizmos: Gizmos) {
+    let Some(spawn) = **spawn else {
+        return;
+    };
+    let wpos = tpos_wpos(&spawn);
+
+    for (start, end) in box_lines(wpos, Vec2::new(16., 16.)) {
+        gizmos.line_2d(start, end, Color::GREEN);
+    }
+    gizmos.line_2d(wpos - Vec2::Y * 6., wpos + Vec2::Y * 6., Color::GREEN);
+}
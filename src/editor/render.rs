@@ -13,7 +13,6 @@ use crate::{
     level::{
         layer::{Layer, ALL_LAYERS},
         placement::StorageAccess,
-        tile::TileKind,
     },
 };
 
@@ -79,6 +78,8 @@ pub fn render_map_images(
     let material = materials.0.get("frame").unwrap();
     let mut data: Vec<u8> = vec![0; texture_format_size * width * height];
     let mut depth: Vec<u8> = vec![30; width * height];
+    // Layer of the closest visible water and whether it is part of the surface
+    let mut water: Vec<Option<(usize, bool)>> = vec![None; width * height];
     for (l, layer) in ALL_LAYERS.iter().enumerate() {
         let map = storage.storage(*layer).unwrap();
         for sub_layer in 0..10 {
@@ -108,6 +109,12 @@ pub fn render_map_images(
                 let offset_idx = offset_x + offset_y * TILE_SIZE as i32 * map_width as i32;
                 let tile_start_offset = (tile_start as i32 + offset_idx) as usize;
 
+                // Top row of a body of water is its surface
+                let water_surface = pos.y + 1 >= map_size.y
+                    || !storage
+                        .get_kind(&TilePos::new(pos.x, pos.y + 1), *layer)
+                        .map_or(false, |kind| kind.is_water());
+
                 (0..TILE_SIZE)
                     .flat_map(move |ty| (0..TILE_SIZE).map(move |tx| (tx, ty)))
                     .for_each(|(tx, ty)| {
@@ -120,8 +127,9 @@ pub fn render_map_images(
                             return;
                         }
 
-                        let dir = match TileKind::from(*id) {
-                            TileKind::Square => {
+                        let kind = storage.registry().get(*id);
+                        let dir = match kind {
+                            Some(kind) if kind.is_solid() => {
                                 let neighbors = Neighbors::get_square_neighboring_positions(
                                     pos, map_size, true,
                                 );
@@ -144,15 +152,12 @@ pub fn render_map_images(
                                     .collect();
                                 material.block.get_pixel(sub_layer, rpos, &neighbors)
                             }
-                            TileKind::Slope => {
+                            Some(kind) if kind.is_slope() => {
                                 let is_solid = |neighbor: Option<&TilePos>| -> bool {
                                     neighbor.map_or(false, |pos| {
-                                        storage.get_properties(&pos, *layer).map_or(
-                                            false,
-                                            |tile_properties| {
-                                                TileKind::from(tile_properties.id).is_solid()
-                                            },
-                                        )
+                                        storage
+                                            .get_kind(&pos, *layer)
+                                            .map_or(false, |kind| kind.is_solid())
                                     })
                                 };
                                 let cardinal = Neighbors::get_square_neighboring_positions(
@@ -174,8 +179,14 @@ pub fn render_map_images(
                                     .slope
                                     .get_pixel(sub_layer, rpos, flip.clone(), &neighbors)
                             }
-                            TileKind::Pole(_) => TilePixel::Neutral,
-                            TileKind::Platform => TilePixel::Neutral,
+                            // Drawn over the finished image, only remember where it is
+                            Some(kind) if kind.is_water() => {
+                                if sub_layer == 0 && water[wpos].is_none() {
+                                    water[wpos] = Some((l, water_surface && ty == 0));
+                                }
+                                TilePixel::None
+                            }
+                            _ => TilePixel::Neutral,
                         };
 
                         let tpos = tile_start + tx + ty * TILE_SIZE * map_width;
@@ -232,6 +243,21 @@ pub fn render_map_images(
         chunk[1] = g;
         chunk[2] = b;
         chunk[3] = a;
+
+        // Water further back is fainter
+        if let Some((layer, surface)) = water[idx] {
+            let water_color = if surface {
+                Color::rgba(0.7, 0.85, 1., 0.9)
+            } else {
+                Color::rgba(0.2, 0.45, 0.85, 0.55 - 0.15 * layer as f32)
+            };
+            let [wr, wg, wb, wa] = water_color.as_rgba_u8();
+            let alpha = wa as f32 / 255.;
+            for (channel, water_channel) in chunk.iter_mut().zip([wr, wg, wb]) {
+                *channel = (*channel as f32 * (1. - alpha) + water_channel as f32 * alpha) as u8;
+            }
+            chunk[3] = chunk[3].max(wa);
+        }
     });
 
     let texture = images.add(make_image(width as u32, height as u32, data));
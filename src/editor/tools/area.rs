@@ -141,6 +141,13 @@ impl<'w, 's> Tool for AreaTool<'w, 's> {
         }
 
         if let (Some(start), Some(end)) = (self.start, self.end) {
+            let Some(id) = tiles
+                .registry()
+                .find(|kind| kind.is_solid())
+                .map(TileTextureIndex::from)
+            else {
+                return;
+            };
             let start = UVec2::from(start);
             let end = UVec2::from(end);
             let (min, max) = (start.min(end), start.max(end));
@@ -152,7 +159,7 @@ impl<'w, 's> Tool for AreaTool<'w, 's> {
                             tiles.replace(
                                 &pos,
                                 TileProperties {
-                                    id: TileTextureIndex(0),
+                                    id,
                                     flip: TileFlip::default(),
                                 },
                                 editor_state.current_layer,
@@ -166,7 +173,7 @@ impl<'w, 's> Tool for AreaTool<'w, 's> {
                             tiles.replace(
                                 &pos,
                                 TileProperties {
-                                    id: TileTextureIndex(0),
+                                    id,
                                     flip: TileFlip::default(),
                                 },
                                 *layer,
@@ -181,7 +188,7 @@ impl<'w, 's> Tool for AreaTool<'w, 's> {
                                 tiles.replace(
                                     &pos,
                                     TileProperties {
-                                        id: TileTextureIndex(0),
+                                        id,
                                         flip: TileFlip::default(),
                                     },
                                     editor_state.current_layer.next(),
@@ -4,16 +4,37 @@ use bevy::{
 };
 use bevy_ecs_tilemap::tiles::{TileFlip, TileTextureIndex};
 
-use crate::{editor::EditorActions, level::placement::TileProperties};
+use crate::{
+    editor::EditorActions,
+    level::{layer::Layer, placement::TileProperties, trigger::TriggerZone},
+};
 
 use super::{
     util::{draw_tile_outline, CommonToolParams},
     Tool,
 };
 
+// Tile kind the paint tool places, defaults to the first solid one
+#[derive(Resource)]
+pub struct PaintBrush {
+    pub kind: Option<u32>,
+    // Name given to painted trigger tiles
+    pub trigger_name: String,
+}
+
+impl Default for PaintBrush {
+    fn default() -> Self {
+        Self {
+            kind: None,
+            trigger_name: "trigger".to_owned(),
+        }
+    }
+}
+
 #[derive(SystemParam)]
 struct PaintToolParams<'w, 's> {
     pub common: CommonToolParams<'w, 's>,
+    pub brush: Res<'w, PaintBrush>,
 }
 
 pub struct PaintTool<'w: 'static, 's: 'static> {
@@ -36,6 +57,7 @@ impl<'w, 's> Tool for PaintTool<'w, 's> {
                     gizmos,
                     editor_actions,
                 },
+            brush,
         } = self.system_state.get_mut(world);
 
         let Some(cursor_tile_pos) = **tile_cursor else {
@@ -48,15 +70,37 @@ impl<'w, 's> Tool for PaintTool<'w, 's> {
             return;
         };
 
+        let registry = tiles.registry();
+        let Some(kind) = brush
+            .kind
+            .and_then(|id| registry.get(TileTextureIndex(id)))
+            .or_else(|| registry.find(|kind| kind.is_solid()))
+        else {
+            return;
+        };
+        let id = TileTextureIndex::from(kind);
+        let is_trigger = kind.is_trigger();
+        // Levels only keep trigger zones on the world layer
+        if is_trigger && editor_state.current_layer != Layer::World {
+            return;
+        }
+
         if editor_actions.pressed(EditorActions::ApplyTool) {
             tiles.replace(
                 &cursor_tile_pos,
                 TileProperties {
-                    id: TileTextureIndex(0),
+                    id,
                     flip: TileFlip::default(),
                 },
                 editor_state.current_layer,
             );
+            if is_trigger {
+                tiles.insert(
+                    &cursor_tile_pos,
+                    editor_state.current_layer,
+                    TriggerZone(brush.trigger_name.clone()),
+                );
+            }
 
             editor_state.unsaved_changes = true;
         }
@@ -48,11 +48,19 @@ impl<'w, 's> Tool for PlatformTool<'w, 's> {
             return;
         };
 
+        let Some(id) = tiles
+            .registry()
+            .find(|kind| kind.is_platform())
+            .map(TileTextureIndex::from)
+        else {
+            return;
+        };
+
         if editor_actions.pressed(EditorActions::ApplyTool) {
             tiles.replace(
                 &cursor_tile_pos,
                 TileProperties {
-                    id: TileTextureIndex(5),
+                    id,
                     flip: TileFlip::default(),
                 },
                 editor_state.current_layer,
@@ -4,7 +4,7 @@ use bevy::{
 };
 use bevy_ecs_tilemap::prelude::*;
 
-use crate::{editor::EditorActions, level::placement::TileProperties};
+use crate::{editor::EditorActions, level::placement::TileProperties, phys::terrain::PoleType};
 
 use super::{
     util::{draw_tile_outline, CommonToolParams},
@@ -55,21 +55,27 @@ impl<'w, 's> Tool for PoleTool<'w, 's> {
         }
 
         if editor_actions.pressed(EditorActions::ApplyTool) {
-            let id: i32 = if self.place_horizontal { 3 } else { 2 };
-            let id = if tiles
-                .get_properties(&cursor_tile_pos, editor_state.current_layer)
-                .map_or(false, |prop| {
-                    prop.id.0 == 4 || (id - prop.id.0 as i32).abs() == 1
-                }) {
-                4
+            let pole = if self.place_horizontal {
+                PoleType::Horizontal
             } else {
-                id
+                PoleType::Vertical
+            };
+            // Placing a pole across an existing one of the other direction combines them
+            let pole = match tiles
+                .get_kind(&cursor_tile_pos, editor_state.current_layer)
+                .and_then(|kind| kind.is_pole())
+            {
+                Some(existing) if existing != pole => PoleType::Combined,
+                _ => pole,
+            };
+            let Some(id) = tiles.registry().pole(pole).map(TileTextureIndex::from) else {
+                return;
             };
 
             tiles.replace(
                 &cursor_tile_pos,
                 TileProperties {
-                    id: TileTextureIndex(id as u32),
+                    id,
                     flip: TileFlip::default(),
                 },
                 editor_state.current_layer,
@@ -4,10 +4,7 @@ use bevy::{
 };
 use bevy_ecs_tilemap::{helpers::square_grid::neighbors::Neighbors, prelude::*};
 
-use crate::{
-    editor::EditorActions,
-    level::{placement::TileProperties, tile::TileKind},
-};
+use crate::{editor::EditorActions, level::placement::TileProperties};
 
 use super::{
     util::{draw_tile_outline, CommonToolParams},
@@ -63,10 +60,8 @@ impl<'w, 's> Tool for SlopeTool<'w, 's> {
             let is_solid = |dir: Option<TilePos>| -> bool {
                 dir.map_or(false, |pos| {
                     tiles
-                        .get_properties(&pos, current_layer)
-                        .map_or(false, |tile_properties| {
-                            TileKind::from(tile_properties.id).is_solid()
-                        })
+                        .get_kind(&pos, current_layer)
+                        .map_or(false, |kind| kind.is_solid())
                 })
             };
 
@@ -87,7 +82,10 @@ impl<'w, 's> Tool for SlopeTool<'w, 's> {
             let mut skip = false;
             if count == 1 {
                 if let Some(properties) = tiles.get_properties(&cursor_tile_pos, current_layer) {
-                    if TileKind::from(properties.id).is_slope() {
+                    let is_slope = tiles
+                        .get_kind(&cursor_tile_pos, current_layer)
+                        .map_or(false, |kind| kind.is_slope());
+                    if is_slope {
                         if editor_actions.just_pressed(EditorActions::ApplyTool) {
                             let old_flip = properties.flip;
                             let new_flip = if north || south {
@@ -116,7 +114,11 @@ impl<'w, 's> Tool for SlopeTool<'w, 's> {
                 }
             }
 
-            if !skip {
+            let slope = tiles
+                .registry()
+                .find(|kind| kind.is_slope())
+                .map(TileTextureIndex::from);
+            if let (false, Some(id)) = (skip, slope) {
                 let mut flip = TileFlip::default();
 
                 if east {
@@ -126,14 +128,7 @@ impl<'w, 's> Tool for SlopeTool<'w, 's> {
                     flip.y = true;
                 }
 
-                tiles.replace(
-                    &cursor_tile_pos,
-                    TileProperties {
-                        id: TileTextureIndex(1),
-                        flip,
-                    },
-                    current_layer,
-                );
+                tiles.replace(&cursor_tile_pos, TileProperties { id, flip }, current_layer);
             }
             editor_state.unsaved_changes = true;
         }
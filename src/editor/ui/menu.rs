@@ -2,7 +2,10 @@ use bevy::prelude::*;
 use bevy_egui::egui;
 
 use crate::{
-    editor::{EditorEvent, EditorState, PickerEvent},
+    editor::{
+        overlay::GridOverlay, play::EditorMode, ui::resize::ResizeMap, EditorEvent, EditorState,
+        PickerEvent,
+    },
     file_picker,
     level::WorldMapExt,
     ui::{
@@ -39,7 +42,15 @@ impl BasicWidget for EditorMenuBar {
                 basic_widget::<Cut>(world, ui, id.with("cut"));
                 basic_widget::<Copy>(world, ui, id.with("copy"));
                 basic_widget::<Paste>(world, ui, id.with("paste"));
+                ui.separator();
+                basic_widget::<ResizeMap>(world, ui, id.with("resize"));
+            });
+            egui::menu::menu_button(ui, "View", |ui| {
+                let id = ui.id().with("view");
+                basic_widget::<ViewToggles>(world, ui, id.with("toggles"));
             });
+            ui.separator();
+            basic_widget::<PlayToggle>(world, ui, ui.id().with("play"));
         });
     }
 }
@@ -291,3 +302,64 @@ impl BasicWidget for Paste {
         }
     }
 }
+
+#[derive(Default, Clone)]
+pub struct ViewToggles;
+
+impl BasicWidget for ViewToggles {
+    fn new(_world: &mut World, _ui: &egui::Ui) -> Self {
+        Self::default()
+    }
+
+    fn draw(&mut self, world: &mut World, ui: &mut egui::Ui, _id: egui::Id) {
+        let mut state = world.resource_mut::<EditorState>();
+        ui.checkbox(&mut state.enabled.minimap, "Minimap");
+
+        let mut overlay = world.resource_mut::<GridOverlay>();
+        ui.checkbox(&mut overlay.show_grid, "Tile Grid");
+        ui.checkbox(&mut overlay.show_rulers, "Rulers");
+        ui.checkbox(&mut overlay.show_rooms, "Room Guides");
+        ui.checkbox(&mut overlay.show_nav, "Navigation Graph");
+        ui.separator();
+        ui.add(
+            egui::DragValue::new(&mut overlay.major_every)
+                .clamp_range(1..=64)
+                .prefix("Major grid every "),
+        );
+        ui.horizontal(|ui| {
+            ui.label("Room size");
+            ui.add(egui::DragValue::new(&mut overlay.room_size.x).clamp_range(1..=256));
+            ui.add(egui::DragValue::new(&mut overlay.room_size.y).clamp_range(1..=256));
+        });
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct PlayToggle;
+
+impl BasicWidget for PlayToggle {
+    fn new(_world: &mut World, _ui: &egui::Ui) -> Self {
+        Self::default()
+    }
+
+    fn draw(&mut self, mut world: &mut World, ui: &mut egui::Ui, _id: egui::Id) {
+        let mode = *world.resource::<State<EditorMode>>().get();
+        let label = match mode {
+            EditorMode::Edit => "Play",
+            EditorMode::Play => "Stop",
+        };
+
+        if world.get_map().is_err() {
+            if ui.add_enabled(false, egui::Button::new(label)).clicked() {
+                unreachable!();
+            }
+            return;
+        };
+
+        if ui.button(label).clicked() {
+            world
+                .resource_mut::<NextState<EditorMode>>()
+                .set(mode.toggled());
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use bevy::{prelude::*, render::texture::ImageSampler};
+use bevy_ecs_tilemap::prelude::*;
+use bevy_egui::{egui, EguiUserTextures};
+use bevy_pancam::PanCam;
+
+use crate::{
+    editor::render::make_image,
+    level::{
+        layer::{Layer, ALL_LAYERS},
+        placement::TileUpdateEvent,
+    },
+    ui::{
+        widget::{fn_widget, BasicWidget},
+        widgets::PanelTitle,
+    },
+};
+
+// One pixel per tile, redrawn only when tiles change
+#[derive(Resource)]
+pub struct MinimapTexture {
+    pub image: Handle<Image>,
+    pub texture_id: egui::TextureId,
+    pub size: UVec2,
+}
+
+pub fn update_minimap_texture(
+    mut cmds: Commands,
+    mut updates: EventReader<TileUpdateEvent>,
+    maps: Query<(Ref<TileStorage>, &TilemapSize, &Layer)>,
+    minimap: Option<Res<MinimapTexture>>,
+    mut images: ResMut<Assets<Image>>,
+    mut egui_user_textures: ResMut<EguiUserTextures>,
+) {
+    let Some(size) = maps
+        .iter()
+        .find(|(_, _, layer)| **layer == Layer::World)
+        .map(|(_, size, _)| UVec2::new(size.x, size.y))
+    else {
+        return;
+    };
+    let tiles_changed =
+        !updates.is_empty() || maps.iter().any(|(storage, _, _)| storage.is_changed());
+    updates.clear();
+    if !tiles_changed && minimap.as_ref().is_some_and(|minimap| minimap.size == size) {
+        return;
+    }
+
+    let mut data = [32, 32, 32, 255].repeat((size.x * size.y) as usize);
+    // Back to front so the world layer ends up on top
+    for layer in ALL_LAYERS.iter().rev() {
+        let color = match layer {
+            Layer::World => [200, 200, 200, 255],
+            Layer::Near => [60, 120, 60, 255],
+            Layer::Far => [100, 40, 40, 255],
+        };
+        for (storage, _, _) in maps.iter().filter(|(_, _, l)| *l == layer) {
+            for (idx, tile) in storage.iter().enumerate() {
+                let (x, y) = (idx as u32 % size.x, idx as u32 / size.x);
+                if tile.is_none() || y >= size.y {
+                    continue;
+                }
+                // Image rows go top to bottom, tile rows bottom to top
+                let pixel = (((size.y - 1 - y) * size.x + x) * 4) as usize;
+                data[pixel..pixel + 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    let mut image = make_image(size.x, size.y, data);
+    image.sampler_descriptor = ImageSampler::nearest();
+    if let Some(minimap) = minimap {
+        egui_user_textures.remove_image(&minimap.image);
+        images.remove(&minimap.image);
+    }
+    let image = images.add(image);
+    let texture_id = egui_user_textures.add_image(image.clone());
+    cmds.insert_resource(MinimapTexture {
+        image,
+        texture_id,
+        size,
+    });
+}
+
+#[derive(Default)]
+pub struct MinimapPanel;
+
+impl BasicWidget for MinimapPanel {
+    fn new(_: &mut World, _: &egui::Ui) -> Self {
+        Self::default()
+    }
+
+    fn draw(&mut self, world: &mut World, ui: &mut egui::Ui, id: egui::Id) {
+        fn_widget::<PanelTitle>(world, ui, id.with("title"), "Minimap");
+
+        let mut maps = world.query::<(&TilemapSize, &Transform, &Layer)>();
+        let Some((map_origin, map_size)) = maps
+            .iter(world)
+            .find(|(_, _, layer)| **layer == Layer::World)
+            .map(|(size, transform, _)| (transform.translation.truncate(), *size))
+        else {
+            ui.label("No map loaded");
+            return;
+        };
+        let Some(texture_id) = world
+            .get_resource::<MinimapTexture>()
+            .map(|minimap| minimap.texture_id)
+        else {
+            return;
+        };
+
+        // Bottom left corner of the map in world space
+        let map_min = map_origin - Vec2::splat(8.);
+        let map_extent = Vec2::from(&map_size) * 16.;
+
+        let width = ui.available_width();
+        let scale = width / map_extent.x;
+        let (response, painter) = ui.allocate_painter(
+            egui::Vec2::new(width, map_extent.y * scale),
+            egui::Sense::click_and_drag(),
+        );
+        let rect = response.rect;
+
+        let to_minimap = |pos: Vec2| -> egui::Pos2 {
+            let rel = (pos - map_min) * scale;
+            egui::Pos2::new(rect.left() + rel.x, rect.bottom() - rel.y)
+        };
+        let to_world = |pos: egui::Pos2| -> Vec2 {
+            map_min + Vec2::new(pos.x - rect.left(), rect.bottom() - pos.y) / scale
+        };
+
+        painter.image(
+            texture_id,
+            rect,
+            egui::Rect::from_min_max(egui::pos2(0., 0.), egui::pos2(1., 1.)),
+            egui::Color32::WHITE,
+        );
+
+        let mut camera =
+            world.query_filtered::<(&mut Transform, &OrthographicProjection), With<PanCam>>();
+        let Ok((mut camera_transform, projection)) = camera.get_single_mut(world) else {
+            return;
+        };
+
+        let camera_pos = camera_transform.translation.truncate();
+        let viewport = egui::Rect::from_two_pos(
+            to_minimap(camera_pos + projection.area.min),
+            to_minimap(camera_pos + projection.area.max),
+        );
+        painter.rect_stroke(viewport, 0., egui::Stroke::new(1., egui::Color32::RED));
+
+        // Jump the camera to the clicked position
+        if response.clicked() || response.dragged() {
+            if let Some(pointer) = response.interact_pointer_pos() {
+                let target = to_world(pointer);
+                camera_transform.translation.x = target.x;
+                camera_transform.translation.y = target.y;
+            }
+        }
+    }
+}
@@ -2,13 +2,19 @@ use bevy::prelude::*;
 
 use crate::{
     editor::{
-        ui::{menu::EditorMenuBar, toolbar::EditorToolBar},
+        ui::{
+            menu::EditorMenuBar,
+            resize::{ResizeDialog, ResizeMapPanel},
+            toolbar::EditorToolBar,
+        },
         EditorState,
     },
     ui,
 };
 
 pub mod menu;
+pub mod minimap;
+pub mod resize;
 pub mod toolbar;
 
 pub fn draw_ui(world: &mut World) {
@@ -25,6 +31,15 @@ pub fn draw_ui(world: &mut World) {
             .default_width(250.)
             .show_animated(ctx, state.enabled.tool_panel, |ui| {
                 basic_widget::<EditorToolBar>(world, ui, ui.id().with("panel"));
-            })
+            });
+
+        if world.contains_resource::<ResizeDialog>() {
+            egui::Window::new("Resize Map")
+                .resizable(false)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    basic_widget::<ResizeMapPanel>(world, ui, ui.id().with("resize"));
+                });
+        }
     });
 }
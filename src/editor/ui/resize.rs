@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_egui::egui;
+
+use crate::{
+    editor::EditorEvent,
+    level::{
+        layer::Layer,
+        placement::{count_cropped, Anchor, ALL_ANCHORS},
+        WorldMapExt,
+    },
+    ui::widget::BasicWidget,
+};
+
+// Exists while the resize dialog is open
+#[derive(Resource)]
+pub struct ResizeDialog {
+    pub size: UVec2,
+    pub anchor: Anchor,
+}
+
+#[derive(Default, Clone)]
+pub struct ResizeMap;
+
+impl BasicWidget for ResizeMap {
+    fn new(_world: &mut World, _ui: &egui::Ui) -> Self {
+        Self::default()
+    }
+
+    fn draw(&mut self, mut world: &mut World, ui: &mut egui::Ui, _id: egui::Id) {
+        if world.get_map().is_err() {
+            if ui
+                .add_enabled(false, egui::Button::new("Resize Map..."))
+                .clicked()
+            {
+                unreachable!();
+            }
+            return;
+        };
+
+        if ui.button("Resize Map...").clicked() {
+            let mut maps = world.query::<(&TilemapSize, &Layer)>();
+            if let Some((size, _)) = maps.iter(world).find(|(_, l)| **l == Layer::World) {
+                let size = UVec2::new(size.x, size.y);
+                world.insert_resource(ResizeDialog {
+                    size,
+                    anchor: Anchor::default(),
+                });
+            }
+            ui.close_menu();
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct ResizeMapPanel;
+
+impl BasicWidget for ResizeMapPanel {
+    fn new(_world: &mut World, _ui: &egui::Ui) -> Self {
+        Self::default()
+    }
+
+    fn draw(&mut self, world: &mut World, ui: &mut egui::Ui, _id: egui::Id) {
+        let Some(dialog) = world.get_resource::<ResizeDialog>() else {
+            return;
+        };
+        let mut size = dialog.size;
+        let mut anchor = dialog.anchor;
+
+        ui.horizontal(|ui| {
+            ui.label("Width");
+            ui.add(egui::DragValue::new(&mut size.x).clamp_range(1..=1024));
+            ui.label("Height");
+            ui.add(egui::DragValue::new(&mut size.y).clamp_range(1..=1024));
+        });
+
+        ui.label("Anchor");
+        egui::Grid::new("resize_anchor").show(ui, |ui| {
+            for (idx, a) in ALL_ANCHORS.iter().enumerate() {
+                ui.selectable_value(&mut anchor, *a, anchor_symbol(a))
+                    .on_hover_text(a.name());
+                if idx % 3 == 2 {
+                    ui.end_row();
+                }
+            }
+        });
+
+        let mut maps = world.query::<(&TileStorage, &TilemapSize)>();
+        let cropped: usize = maps
+            .iter(world)
+            .map(|(storage, old_size)| count_cropped(storage, old_size, size, anchor))
+            .sum();
+        if cropped > 0 {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("Warning: {} tiles will be cropped", cropped),
+            );
+        }
+
+        let mut close = false;
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+            if ui.button("Resize").clicked() {
+                world.send_event(EditorEvent::Resize(size, anchor));
+                close = true;
+            }
+            if ui.button("Cancel").clicked() {
+                close = true;
+            }
+        });
+
+        if close {
+            world.remove_resource::<ResizeDialog>();
+        } else {
+            let mut dialog = world.resource_mut::<ResizeDialog>();
+            dialog.size = size;
+            dialog.anchor = anchor;
+        }
+    }
+}
+
+fn anchor_symbol(anchor: &Anchor) -> &'static str {
+    use Anchor::*;
+    match anchor {
+        TopLeft => "↖",
+        Top => "↑",
+        TopRight => "↗",
+        Left => "←",
+        Center => "•",
+        Right => "→",
+        BottomLeft => "↙",
+        Bottom => "↓",
+        BottomRight => "↘",
+    }
+}
@@ -3,10 +3,17 @@ use bevy_egui::egui;
 
 use crate::{
     editor::{
-        tools::area::{ActiveMode, ALL_MODES},
+        tools::{
+            area::{ActiveMode, ALL_MODES},
+            paint::PaintBrush,
+        },
+        ui::minimap::MinimapPanel,
         EditorState,
     },
-    level::layer::ALL_LAYERS,
+    level::{
+        layer::{Layer, ALL_LAYERS},
+        tile::TileRegistry,
+    },
     ui::{
         widget::{basic_widget, fn_widget, BasicWidget},
         widgets::PanelTitle,
@@ -30,6 +37,12 @@ impl BasicWidget for EditorToolBar {
         ui.separator();
         basic_widget::<LayersPanel>(world, ui, id.with("layers"));
         ui.separator();
+        if world.resource::<EditorState>().enabled.minimap {
+            basic_widget::<MinimapPanel>(world, ui, id.with("minimap"));
+            ui.separator();
+        }
+        basic_widget::<PaintToolPanel>(world, ui, id.with("paint_tool"));
+        ui.separator();
         basic_widget::<AreaToolPanel>(world, ui, id.with("area_tool"));
     }
 }
@@ -149,3 +162,57 @@ impl BasicWidget for AreaToolPanel {
             });
     }
 }
+
+#[derive(Default)]
+pub struct PaintToolPanel;
+
+impl BasicWidget for PaintToolPanel {
+    fn new(_: &mut World, _: &egui::Ui) -> Self {
+        Self::default()
+    }
+
+    fn draw(&mut self, world: &mut World, ui: &mut egui::Ui, id: egui::Id) {
+        fn_widget::<PanelTitle>(world, ui, id.with("title"), "Paint Tile");
+        let kinds: Vec<(u32, String, bool)> = world
+            .resource::<TileRegistry>()
+            .iter()
+            .map(|kind| (kind.id, kind.name(), kind.is_trigger()))
+            .collect();
+
+        let brush = world.resource::<PaintBrush>();
+        let mut current = brush.kind;
+        let mut trigger_name = brush.trigger_name.clone();
+        let mut changed = false;
+
+        let layout = egui::Layout::top_down(egui::Align::LEFT).with_cross_justify(true);
+        ui.with_layout(layout, |ui| {
+            for (kind_id, name, _) in kinds.iter() {
+                changed |= ui
+                    .selectable_value(&mut current, Some(*kind_id), name)
+                    .changed();
+            }
+        });
+
+        let is_trigger = kinds
+            .iter()
+            .any(|(kind_id, _, trigger)| *trigger && current == Some(*kind_id));
+        if is_trigger {
+            let on_world = world.resource::<EditorState>().current_layer == Layer::World;
+            ui.add_enabled_ui(on_world, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Trigger name");
+                    changed |= ui.text_edit_singleline(&mut trigger_name).changed();
+                });
+            });
+            if !on_world {
+                ui.weak("Triggers can only be painted on the world layer");
+            }
+        }
+
+        if changed {
+            let mut brush = world.resource_mut::<PaintBrush>();
+            brush.kind = current;
+            brush.trigger_name = trigger_name;
+        }
+    }
+}
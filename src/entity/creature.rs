@@ -0,0 +1,236 @@
+use bevy::{ecs::system::Command, prelude::*, time::Stopwatch};
+use bevy_xpbd_2d::{
+    math::{Scalar, Vector},
+    prelude::*,
+};
+
+use crate::{
+    level::nav::{NavGraph, NavLink, NavPath},
+    phys::{
+        movement::{
+            ActionKind, CrouchShape, LookDir, MovementMode, MovementState, PlayerState,
+            VirtualControl, VirtualController,
+        },
+        terrain::{PlatformPass, Pole, Terrain},
+    },
+};
+
+use super::player::Player;
+
+// How long a creature keeps chasing after losing sight of its target
+pub const CREATURE_FORGET_TIME: f32 = 3.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum AiState {
+    // Walks back and forth, turning around at walls and ledges
+    Patrol(LookDir),
+    Chase { target: Entity, last_seen: Vector },
+}
+
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct CreatureAi {
+    pub state: AiState,
+    pub sight_range: Scalar,
+    #[reflect(ignore)]
+    pub lost_sight: Stopwatch,
+    #[reflect(ignore)]
+    pub path: Option<NavPath>,
+}
+
+impl Default for CreatureAi {
+    fn default() -> Self {
+        Self {
+            state: AiState::Patrol(LookDir::Right),
+            sight_range: 160.,
+            lost_sight: Stopwatch::new(),
+            path: None,
+        }
+    }
+}
+
+pub struct SpawnCreatureCommand {
+    pub pos: Vector,
+    pub size: Vector,
+}
+
+impl Command for SpawnCreatureCommand {
+    fn apply(self, world: &mut World) {
+        // Same body as the player so it moves with the same physics
+        world.spawn((
+            Name::new("Creature"),
+            Position(self.pos),
+            Collider::cuboid(self.size.x, self.size.y),
+            RigidBody::Dynamic,
+            LockedAxes::new().lock_rotation(),
+            ShapeCaster::new(
+                Collider::cuboid(self.size.x - 0.5, self.size.y - 0.5),
+                Vector::NEG_Y * 0.05,
+                0.,
+                Vector::NEG_Y,
+            )
+            .with_ignore_origin_penetration(true)
+            .with_max_time_of_impact(0.2)
+            .with_max_hits(1),
+            VirtualControl::default(),
+            CreatureAi::default(),
+            Friction::new(0.5),
+            LookDir::Right,
+            CrouchShape::new(self.size),
+            PlatformPass,
+            GravityScale(1.),
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgb(0.8, 0.3, 0.3),
+                    custom_size: Some(self.size),
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+    }
+}
+
+// Distance to the closest terrain along the ray, other colliders don't block
+fn terrain_hit(
+    spatial_query: &SpatialQuery,
+    terrain: &Query<(), With<Terrain>>,
+    origin: Vector,
+    direction: Vector,
+    max_toi: Scalar,
+) -> Option<Scalar> {
+    spatial_query
+        .ray_hits(
+            origin,
+            direction,
+            max_toi,
+            8,
+            true,
+            SpatialQueryFilter::default(),
+        )
+        .into_iter()
+        .filter(|hit| terrain.contains(hit.entity))
+        .map(|hit| hit.time_of_impact)
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+// Decides which buttons the creature holds this tick
+pub fn creature_ai(
+    mut creatures: Query<(
+        &mut CreatureAi,
+        &mut VirtualController,
+        &Position,
+        &ColliderAabb,
+        &MovementState,
+        &PlayerState,
+        &CollidingEntities,
+    )>,
+    players: Query<(Entity, &Position), With<Player>>,
+    poles: Query<(), With<Pole>>,
+    terrain: Query<(), With<Terrain>>,
+    spatial_query: SpatialQuery,
+    nav: Option<Res<NavGraph>>,
+    time: Res<FixedTime>,
+) {
+    use ActionKind::*;
+
+    for (mut ai, mut controller, pos, aabb, movement_state, state, colliding) in
+        creatures.iter_mut()
+    {
+        // Jump has to be let go for a tick before it registers again
+        let was_jumping = controller.pressed(Jump);
+        controller.release_all();
+
+        let visible = players.iter().find(|(_, player_pos)| {
+            let offset = player_pos.0 - pos.0;
+            let distance = offset.length();
+            distance <= ai.sight_range
+                && terrain_hit(
+                    &spatial_query,
+                    &terrain,
+                    pos.0,
+                    offset.normalize_or_zero(),
+                    distance,
+                )
+                .is_none()
+        });
+
+        if let Some((target, player_pos)) = visible {
+            ai.state = AiState::Chase {
+                target,
+                last_seen: player_pos.0,
+            };
+            ai.lost_sight.reset();
+        } else if let AiState::Chase { .. } = ai.state {
+            ai.lost_sight.tick(time.period);
+            if ai.lost_sight.elapsed_secs() >= CREATURE_FORGET_TIME {
+                ai.state = AiState::Patrol(LookDir::Right);
+                ai.path = None;
+            }
+        }
+
+        let half_extents = aabb.half_extents();
+        match ai.state {
+            AiState::Patrol(dir) => {
+                // Turn around at walls and before walking off ledges
+                let ahead = pos.0 + dir.as_vec() * (half_extents.x + 2.);
+                let ledge = movement_state.grounded
+                    && terrain_hit(
+                        &spatial_query,
+                        &terrain,
+                        ahead,
+                        Vector::NEG_Y,
+                        half_extents.y + 8.,
+                    )
+                    .is_none();
+                let dir = if movement_state.facing_wall || ledge {
+                    dir.opposite()
+                } else {
+                    dir
+                };
+                ai.state = AiState::Patrol(dir);
+                controller.press(dir.as_action_kind());
+            }
+            AiState::Chase { last_seen, .. } => {
+                // Follow the navigation graph if there is a way, otherwise head straight for it
+                let step = nav
+                    .as_ref()
+                    .and_then(|nav| nav.follow(&mut ai.path, pos.0, last_seen));
+                let (goal, jump_link) = match step {
+                    Some(step) => (NavGraph::cell_center(step.cell), step.link == NavLink::Jump),
+                    None => (last_seen, false),
+                };
+                let offset = goal - pos.0;
+                let horizontal = if offset.x < 0. { Left } else { Right };
+
+                if let MovementMode::PoleClimb(_) = state.mode() {
+                    if offset.y.abs() > 8. {
+                        controller.press(if offset.y > 0. { Up } else { Down });
+                    } else if offset.x.abs() > 8. {
+                        // Level with the target, jump off towards it
+                        controller.press(horizontal);
+                        controller.set(Jump, !was_jumping);
+                    }
+                    continue;
+                }
+
+                if offset.x.abs() > 4. {
+                    controller.press(horizontal);
+                }
+                // Climb towards targets above when touching a pole
+                let on_pole = colliding.0.iter().any(|other| poles.contains(*other));
+                if on_pole && offset.y > 16. {
+                    controller.press(Up);
+                    continue;
+                }
+                let blocked = movement_state.facing_wall && offset.x.abs() > 4.;
+                if movement_state.grounded && (blocked || jump_link || offset.y > 24.) {
+                    controller.set(Jump, !was_jumping);
+                } else if was_jumping && !movement_state.grounded {
+                    // Keep holding for the full jump height
+                    controller.press(Jump);
+                }
+            }
+        }
+    }
+}
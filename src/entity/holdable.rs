@@ -1,87 +1,345 @@
-use bevy::prelude::*;
-use bevy_xpbd_2d::{math::Vector, prelude::*};
+use bevy::{ecs::system::Command, prelude::*, time::Stopwatch};
+use bevy_xpbd_2d::{
+    math::{Scalar, Vector},
+    prelude::*,
+};
 
-use crate::phys::movement::LookDir;
+use crate::{
+    level::trigger::PlayerHurt,
+    lifetime::Lifetime,
+    phys::{
+        movement::{ActionKind, LookDir, MovementInput, MovementMode, PlayerState},
+        water::{Density, Submerged},
+    },
+};
 
-use super::pebble::SpawnPebble;
+use super::{pebble::PEBBLE_DENSITY, player::Player, projectile::Thrown};
 
-#[derive(Component)]
-pub struct Holdable;
+// Throw speed without charging and fully charged
+pub const THROW_MIN_SPEED: Scalar = 192.;
+pub const THROW_MAX_SPEED: Scalar = 512.;
+// How long the throw button has to be held for a full charge
+pub const THROW_CHARGE_TIME: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum HoldableShape {
+    Ball(Scalar),
+    Cuboid(Vector),
+}
+
+// What happens once the holdable leaves the holder's hands
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum ThrowBehaviour {
+    Bounce,
+    // Gets stuck in the first terrain it hits at least this fast, pointing where it flew
+    Stick {
+        min_speed: Scalar,
+    },
+    // Throwing lights the fuse, explodes once its lifetime runs out
+    Fuse {
+        time: f32,
+        radius: Scalar,
+        impulse: Scalar,
+    },
+}
+
+// Describes an object that can be carried and thrown
+#[derive(Component, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct Holdable {
+    pub name: String,
+    pub mass: Scalar,
+    // Relative to water, decides whether it floats or sinks
+    pub density: Scalar,
+    pub shape: HoldableShape,
+    pub color: Color,
+    pub throw: ThrowBehaviour,
+}
+
+impl Holdable {
+    pub fn pebble() -> Self {
+        Self {
+            name: "Pebble".to_string(),
+            mass: 1.,
+            density: PEBBLE_DENSITY,
+            shape: HoldableShape::Ball(4.),
+            color: Color::rgb(0.7, 0.7, 0.8),
+            throw: ThrowBehaviour::Bounce,
+        }
+    }
+
+    pub fn spear() -> Self {
+        Self {
+            name: "Spear".to_string(),
+            mass: 2.,
+            density: 0.8,
+            shape: HoldableShape::Cuboid(Vector::new(20., 2.)),
+            color: Color::rgb(0.6, 0.45, 0.3),
+            throw: ThrowBehaviour::Stick { min_speed: 0. },
+        }
+    }
+
+    pub fn bomb() -> Self {
+        Self {
+            name: "Bomb".to_string(),
+            mass: 3.,
+            density: 3.,
+            shape: HoldableShape::Ball(5.),
+            color: Color::rgb(0.2, 0.2, 0.25),
+            throw: ThrowBehaviour::Fuse {
+                time: 2.,
+                radius: 48.,
+                impulse: 384.,
+            },
+        }
+    }
 
+    pub fn collider(&self) -> Collider {
+        match self.shape {
+            HoldableShape::Ball(radius) => Collider::ball(radius),
+            HoldableShape::Cuboid(size) => Collider::cuboid(size.x, size.y),
+        }
+    }
+
+    pub fn size(&self) -> Vector {
+        match self.shape {
+            HoldableShape::Ball(radius) => Vector::splat(radius * 2.),
+            HoldableShape::Cuboid(size) => size,
+        }
+    }
+}
+
+pub struct SpawnHoldable {
+    pub holdable: Holdable,
+    pub pos: Vector,
+    pub vel: Vector,
+    pub lifetime: Option<f32>,
+}
+
+impl Command for SpawnHoldable {
+    fn apply(self, world: &mut World) {
+        let holdable = self.holdable;
+        let id = world
+            .spawn((
+                Position(self.pos),
+                LinearVelocity(self.vel),
+                RigidBody::Dynamic,
+                holdable.collider(),
+                Mass(holdable.mass),
+                Density(holdable.density),
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: holdable.color,
+                        custom_size: Some(holdable.size()),
+                        ..default()
+                    },
+                    ..default()
+                },
+                Name::new(holdable.name.clone()),
+                holdable,
+            ))
+            .id();
+        if let Some(lifetime) = self.lifetime {
+            world.entity_mut(id).insert(Lifetime::new(lifetime));
+        }
+    }
+}
+
+// Points to the entity carrying this one
 #[derive(Component)]
-pub struct IsHeld;
+pub struct IsHeld(pub Entity);
 
 #[derive(Component)]
 pub struct CanHold;
 
+// Entity currently being carried
+#[derive(Component)]
+pub struct Holding(pub Entity);
+
+// Exists while the throw button is held down
+#[derive(Component, Default)]
+pub struct ThrowCharge(pub Stopwatch);
+
+impl ThrowCharge {
+    // Fraction of a full charge
+    pub fn strength(&self) -> Scalar {
+        (self.0.elapsed_secs() / THROW_CHARGE_TIME).min(1.)
+    }
+}
+
 pub fn pick_up(
     mut cmds: Commands,
-    keys: Res<Input<KeyCode>>,
-    holdables: Query<Entity, With<Holdable>>,
-    held: Query<&IsHeld>,
-    holder: Query<(Entity, Option<&Children>, &CollidingEntities), With<CanHold>>,
+    holdables: Query<(), (With<Holdable>, Without<IsHeld>)>,
+    mut creatures: Query<&mut PlayerState>,
+    holder: Query<(Entity, &CollidingEntities, &MovementInput), (With<CanHold>, Without<Holding>)>,
 ) {
-    let Ok((holder, children, colliding)) = holder.get_single() else {
-        return;
-    };
+    for (holder, colliding, input) in holder.iter() {
+        if !input.just_pressed(ActionKind::PickUp) {
+            continue;
+        }
+        // Find first colliding enitty that is also a holdable
+        let Some(holdable) = colliding.0.iter().find(|e| holdables.contains(**e)) else {
+            continue;
+        };
+
+        // Keep the entity around but take it out of the simulation while it is carried
+        cmds.entity(*holdable)
+            .insert((IsHeld(holder), RigidBody::Kinematic, Sensor))
+            .remove::<(Submerged, Thrown)>();
+        cmds.entity(holder).insert(Holding(*holdable));
 
-    if let Some(children) = children {
-        if children.iter().any(|child| held.get(*child).is_ok()) {
-            return;
+        if let Ok(mut state) = creatures.get_mut(*holdable) {
+            state.set(MovementMode::Held);
         }
     }
+}
 
-    if keys.just_pressed(KeyCode::H) {
-        // Find first colliding enitty that is also a holdable
-        if let Some(holdable) = colliding.0.iter().find(|e| holdables.get(**e).is_ok()) {
-            // Despawn entity to get rid of all the physics related components
-            // TODO when bevy_xpbd supports child colliders think about simply moving the entity to the children list
-            // This would hopefully also naturally add velocity inheritance on throwing
-            cmds.entity(*holdable).despawn();
-            cmds.entity(holder).with_children(|childbuilder| {
-                childbuilder.spawn((
-                    IsHeld,
-                    SpriteBundle {
-                        sprite: Sprite {
-                            color: Color::rgb(0.7, 0.7, 0.8),
-                            custom_size: Some(Vec2::splat(8.0)),
-                            ..default()
-                        },
-                        ..default()
-                    },
-                ));
-            });
-        }
+// Keeps carried entities above their holder
+pub fn carry_held(
+    mut cmds: Commands,
+    holders: Query<(Entity, &Position, &ColliderAabb, &Holding), Without<IsHeld>>,
+    mut held: Query<(&mut Position, &mut LinearVelocity), With<IsHeld>>,
+) {
+    for (holder, holder_pos, holder_aabb, holding) in holders.iter() {
+        let Ok((mut pos, mut vel)) = held.get_mut(holding.0) else {
+            // Carried entity is gone, e.g. a bomb that exploded in the holder's hands
+            cmds.entity(holder).remove::<(Holding, ThrowCharge)>();
+            continue;
+        };
+        pos.0 = holder_pos.0 + Vector::Y * (holder_aabb.half_extents().y + 4.);
+        vel.0 = Vector::ZERO;
+    }
+}
+
+// Direction to throw in, holding up or down aims higher or lower
+fn throw_aim(look_dir: &LookDir, input: &MovementInput) -> Vector {
+    let forward = look_dir.as_vec();
+    if input.pressed(ActionKind::Up) {
+        (forward * 0.3 + Vector::Y).normalize()
+    } else if input.pressed(ActionKind::Down) {
+        (forward * 0.3 - Vector::Y).normalize()
+    } else {
+        (forward + Vector::Y * 0.25).normalize()
     }
 }
 
+// Runs on fixed ticks so throws are part of the deterministic simulation
 pub fn throw(
     mut cmds: Commands,
-    keys: Res<Input<KeyCode>>,
-    holder: Query<(Entity, &Children, &Transform, &LookDir), With<CanHold>>,
-    held: Query<(With<IsHeld>, With<Parent>)>,
+    time: Res<FixedTime>,
+    mut holder: Query<
+        (
+            Entity,
+            &Holding,
+            &Position,
+            &LinearVelocity,
+            &ColliderAabb,
+            &LookDir,
+            &MovementInput,
+            Option<&mut ThrowCharge>,
+        ),
+        Without<IsHeld>,
+    >,
+    mut held: Query<
+        (
+            &Holdable,
+            &mut Position,
+            &mut Rotation,
+            &mut LinearVelocity,
+            Has<Lifetime>,
+        ),
+        With<IsHeld>,
+    >,
+    mut creatures: Query<&mut PlayerState, With<IsHeld>>,
+) {
+    for (holder, holding, holder_pos, holder_vel, holder_aabb, look_dir, input, charge) in
+        holder.iter_mut()
+    {
+        // Hold to charge, release to throw
+        let Some(mut charge) = charge else {
+            if input.pressed(ActionKind::Throw) {
+                cmds.entity(holder).insert(ThrowCharge::default());
+            }
+            continue;
+        };
+        charge.0.tick(time.period);
+        if input.pressed(ActionKind::Throw) {
+            continue;
+        }
+
+        let aim = throw_aim(look_dir, input);
+        let speed = THROW_MIN_SPEED + (THROW_MAX_SPEED - THROW_MIN_SPEED) * charge.strength();
+
+        cmds.entity(holder).remove::<(Holding, ThrowCharge)>();
+        let Ok((holdable, mut pos, mut rot, mut vel, has_lifetime)) = held.get_mut(holding.0)
+        else {
+            continue;
+        };
+        // Start outside of the holder so the two don't collide
+        let holder_extent = holder_aabb.half_extents().norm();
+        let item_extent = holdable.size().max_element() / 2.;
+        pos.0 = holder_pos.0 + aim * (holder_extent + item_extent + 1.);
+        vel.0 = holder_vel.0 + aim * speed;
+        *rot = Rotation::from_radians(vel.y.atan2(vel.x));
+
+        let mut thrown = cmds.entity(holding.0);
+        thrown
+            .remove::<(IsHeld, Sensor)>()
+            .insert((RigidBody::Dynamic, Thrown::new(holder, pos.0, vel.0)));
+        if let ThrowBehaviour::Fuse { time, .. } = holdable.throw {
+            if !has_lifetime {
+                thrown.insert(Lifetime::new(time));
+            }
+        }
+
+        if let Ok(mut state) = creatures.get_mut(holding.0) {
+            state.set(MovementMode::Airborne);
+        }
+    }
+}
+
+// Runs right before lifetimes despawn their entities
+pub fn explode_bombs(
+    bombs: Query<(Entity, &Holdable, &Lifetime, &Position)>,
+    mut bodies: Query<(&Position, &RigidBody, &mut LinearVelocity)>,
+    players: Query<(), With<Player>>,
+    spatial_query: SpatialQuery,
+    time: Res<FixedTime>,
+    mut hurt: EventWriter<PlayerHurt>,
 ) {
-    let Ok((holder, children, transform, look_dir)) = holder.get_single() else {
-        return;
-    };
-
-    // Can't throw anything
-    let Some(throwable) = children.iter().find(|child| held.get(**child).is_ok()) else {
-        return;
-    };
-
-    if keys.just_pressed(KeyCode::X) {
-        // Remove child throwable entity
-        cmds.entity(holder).remove_children(&[*throwable]);
-        cmds.entity(*throwable).despawn();
-
-        // Spawn new physics entity
-        let pos = transform.translation.truncate() + look_dir.as_vec() * 16.; // Dont hardcode player size
-        let vel = look_dir.as_vec() * 512. + Vector::Y * 128.;
-        cmds.add(SpawnPebble {
-            pos,
-            vel,
-            lifetime: None,
-        });
+    for (bomb, holdable, lifetime, bomb_pos) in bombs.iter() {
+        let ThrowBehaviour::Fuse {
+            radius, impulse, ..
+        } = holdable.throw
+        else {
+            continue;
+        };
+        if !lifetime.will_expire(time.period) {
+            continue;
+        }
+
+        for entity in spatial_query.shape_intersections(
+            &Collider::ball(radius),
+            bomb_pos.0,
+            0.,
+            SpatialQueryFilter::new().without_entities([bomb]),
+        ) {
+            if players.contains(entity) {
+                hurt.send(PlayerHurt {
+                    player: entity,
+                    source: bomb,
+                });
+            }
+            let Ok((pos, rb, mut vel)) = bodies.get_mut(entity) else {
+                continue;
+            };
+            if !rb.is_dynamic() {
+                continue;
+            }
+            // Weaker further away from the center
+            let offset = pos.0 - bomb_pos.0;
+            let falloff = 1. - (offset.length() / radius).min(1.);
+            vel.0 += offset.normalize_or_zero() * impulse * falloff;
+        }
     }
 }
@@ -1,3 +1,5 @@
+pub mod creature;
 pub mod holdable;
 pub mod pebble;
 pub mod player;
+pub mod projectile;
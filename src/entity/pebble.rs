@@ -1,31 +1,29 @@
 use bevy::{ecs::system::Command, prelude::*};
-use bevy_xpbd_2d::prelude::*;
 
-use crate::lifetime::Lifetime;
-
-use super::holdable::Holdable;
+use super::holdable::{Holdable, SpawnHoldable};
 
 pub struct SpawnPebble {
     pub pos: Vec2,
     pub vel: Vec2,
     pub lifetime: Option<f32>,
+    // Relative to water, decides whether the pebble floats or sinks
+    pub density: f32,
 }
 
+// Sinks in water
+pub const PEBBLE_DENSITY: f32 = 2.5;
+
 impl Command for SpawnPebble {
     fn apply(self, world: &mut World) {
-        let id = world
-            .spawn((
-                Position(self.pos),
-                LinearVelocity(self.vel),
-                RigidBody::Dynamic,
-                Collider::ball(4.),
-                Holdable,
-                SpatialBundle::default(),
-                Name::new("Pebble"),
-            ))
-            .id();
-        if let Some(lifetime) = self.lifetime {
-            world.entity_mut(id).insert(Lifetime::new(lifetime));
+        SpawnHoldable {
+            holdable: Holdable {
+                density: self.density,
+                ..Holdable::pebble()
+            },
+            pos: self.pos,
+            vel: self.vel,
+            lifetime: self.lifetime,
         }
+        .apply(world);
     }
 }
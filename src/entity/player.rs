@@ -5,7 +5,7 @@ use bevy::{
 use bevy_xpbd_2d::{math::Vector, prelude::*};
 
 use crate::phys::{
-    movement::{Control, LookDir},
+    movement::{Control, CrouchShape, LookDir},
     terrain::PlatformPass,
 };
 
@@ -20,6 +20,10 @@ pub struct PlayerQuery {
 #[derive(Component)]
 pub struct Player;
 
+// Where the player is put back after getting hurt
+#[derive(Component, Deref, DerefMut)]
+pub struct Respawn(pub Vector);
+
 pub struct SpawnPlayerCommand<B: Bundle> {
     pub pos: Vector,
     pub size: Vector,
@@ -37,6 +41,7 @@ impl<B: Bundle> Command for SpawnPlayerCommand<B> {
         world.spawn((
             (Name::new("Player"), Player),
             Position(self.pos),
+            Respawn(self.pos),
             Collider::cuboid(self.size.x, self.size.y),
             RigidBody::Dynamic,
             LockedAxes::new().lock_rotation(),
@@ -53,6 +58,7 @@ impl<B: Bundle> Command for SpawnPlayerCommand<B> {
             Control::default(),
             Friction::new(0.5),
             LookDir::Right,
+            CrouchShape::new(self.size),
             PlatformPass,
             GravityScale(1.),
             CanHold,
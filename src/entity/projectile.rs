@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use bevy_xpbd_2d::{
+    math::{Scalar, Vector},
+    prelude::*,
+};
+
+use crate::{
+    phys::{
+        movement::{MovementMode, PlayerState},
+        terrain::Terrain,
+        verlet::{Link, Point},
+    },
+    util::segments_intersect,
+};
+
+use super::holdable::{Holdable, ThrowBehaviour};
+
+// Fraction of the projectile's momentum that is passed on to whatever it hits
+pub const KNOCKBACK: Scalar = 0.5;
+// Creatures hit faster than this lose control for a moment
+pub const STUN_SPEED: Scalar = 256.;
+
+// Flying after being thrown, until it hits something
+#[derive(Component)]
+pub struct Thrown {
+    // Never hits the one who threw it
+    pub thrower: Entity,
+    // State before the last physics step, collisions already changed the current one
+    pub last_position: Vector,
+    pub last_velocity: Vector,
+}
+
+impl Thrown {
+    pub fn new(thrower: Entity, pos: Vector, vel: Vector) -> Self {
+        Self {
+            thrower,
+            last_position: pos,
+            last_velocity: vel,
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct ProjectileHit {
+    pub projectile: Entity,
+    pub target: Entity,
+    // Relative speed at the moment of impact
+    pub speed: Scalar,
+}
+
+pub fn track_thrown(
+    mut thrown: Query<(
+        &Holdable,
+        &mut Thrown,
+        &Position,
+        &LinearVelocity,
+        &mut Rotation,
+    )>,
+) {
+    for (holdable, mut thrown, pos, vel, mut rot) in thrown.iter_mut() {
+        thrown.last_position = pos.0;
+        thrown.last_velocity = vel.0;
+
+        // Sticking projectiles point where they fly
+        if let ThrowBehaviour::Stick { .. } = holdable.throw {
+            if vel.0 != Vector::ZERO {
+                *rot = Rotation::from_radians(vel.y.atan2(vel.x));
+            }
+        }
+    }
+}
+
+pub fn detect_projectile_hits(
+    mut collisions: EventReader<CollisionStarted>,
+    thrown: Query<&Thrown>,
+    velocities: Query<&LinearVelocity>,
+    sensors: Query<(), With<Sensor>>,
+    mut hits: EventWriter<ProjectileHit>,
+) {
+    for CollisionStarted(a, b) in collisions.iter() {
+        for (projectile, target) in [(*a, *b), (*b, *a)] {
+            let Ok(thrown) = thrown.get(projectile) else {
+                continue;
+            };
+            // Flying through water, poles or triggers isn't a hit
+            if sensors.contains(target) || target == thrown.thrower {
+                continue;
+            }
+            let target_vel = velocities.get(target).map_or(Vector::ZERO, |vel| vel.0);
+            hits.send(ProjectileHit {
+                projectile,
+                target,
+                speed: (thrown.last_velocity - target_vel).length(),
+            });
+        }
+    }
+}
+
+pub fn apply_projectile_hits(
+    mut cmds: Commands,
+    mut hits: EventReader<ProjectileHit>,
+    mut projectiles: Query<(&Holdable, &Thrown, &mut LinearVelocity, Option<&Mass>)>,
+    mut targets: Query<
+        (
+            &RigidBody,
+            &mut LinearVelocity,
+            Option<&Mass>,
+            Option<&mut PlayerState>,
+        ),
+        Without<Thrown>,
+    >,
+    terrain: Query<(), With<Terrain>>,
+) {
+    for hit in hits.iter() {
+        let Ok((holdable, thrown, mut projectile_vel, projectile_mass)) =
+            projectiles.get_mut(hit.projectile)
+        else {
+            continue;
+        };
+        // Only the first hit of a throw counts
+        cmds.entity(hit.projectile).remove::<Thrown>();
+
+        if terrain.contains(hit.target) {
+            if let ThrowBehaviour::Stick { min_speed } = holdable.throw {
+                if hit.speed >= min_speed {
+                    projectile_vel.0 = Vector::ZERO;
+                    cmds.entity(hit.projectile).insert(RigidBody::Static);
+                }
+            }
+            continue;
+        }
+
+        let Ok((rb, mut vel, target_mass, state)) = targets.get_mut(hit.target) else {
+            continue;
+        };
+        if !rb.is_dynamic() {
+            continue;
+        }
+
+        let mass_ratio = projectile_mass.map_or(1., |mass| mass.0)
+            / target_mass.map_or(1., |mass| mass.0).max(Scalar::EPSILON);
+        vel.0 += thrown.last_velocity.normalize_or_zero() * hit.speed * KNOCKBACK * mass_ratio;
+
+        if let Some(mut state) = state {
+            if hit.speed >= STUN_SPEED {
+                state.set(MovementMode::Stunned);
+            }
+        }
+    }
+}
+
+// Cuts verlet links a projectile flew through during the last step
+pub fn break_links(
+    mut cmds: Commands,
+    thrown: Query<(&Thrown, &Position)>,
+    links: Query<(Entity, &Link)>,
+    points: Query<&Transform, With<Point>>,
+) {
+    for (thrown, pos) in thrown.iter() {
+        let path = (thrown.last_position, pos.0);
+        for (entity, link) in links.iter() {
+            let Ok([a, b]) = points.get_many([link.a, link.b]) else {
+                continue;
+            };
+            let segment = (a.translation.truncate(), b.translation.truncate());
+            if segments_intersect(path, segment) {
+                cmds.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use bevy::{
+    ecs::system::Command,
+    prelude::*,
+    utils::hashbrown::{HashMap, HashSet},
+};
+use bevy_ecs_tilemap::prelude::*;
+use bevy_xpbd_2d::{math::Scalar, prelude::*};
+
+use crate::{grid::Grid, phys::terrain::Terrain};
+
+use super::{
+    layer::Layer,
+    placement::{StorageAccess, TileModification, TileProperties, TileUpdateEvent},
+    tile::{InsertTileColliderCommand, TileRegistry},
+    tpos_wpos,
+};
+
+// Full solid blocks are merged into compound colliders per chunk, one for each friction value
+pub const CHUNK_SIZE: u32 = 16;
+
+// While this resource exists colliders of the world layer follow changes to the map
+#[derive(Resource, Default)]
+pub struct TileColliders {
+    chunks: HashMap<UVec2, Vec<Entity>>,
+    dirty: HashSet<UVec2>,
+}
+
+impl TileColliders {
+    pub fn mark_dirty(&mut self, pos: &TilePos) {
+        self.dirty.insert(UVec2::new(pos.x, pos.y) / CHUNK_SIZE);
+    }
+
+    pub fn mark_all_dirty(&mut self, size: &TilemapSize) {
+        let chunks = (UVec2::new(size.x, size.y) + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        for x in 0..chunks.x {
+            for y in 0..chunks.y {
+                self.dirty.insert(UVec2::new(x, y));
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TerrainChunk;
+
+// Inserts colliders for every tile of the world layer and keeps them updated from then on
+pub struct InsertMapCollidersCommand;
+
+impl Command for InsertMapCollidersCommand {
+    fn apply(self, world: &mut World) {
+        let mut tiles =
+            world.query::<(Entity, &TilePos, &TileTextureIndex, &TileFlip, &TilemapId)>();
+        let mut layers = world.query::<(&Layer, &TilemapSize)>();
+
+        let Some(size) = layers
+            .iter(world)
+            .find(|(layer, _)| **layer == Layer::World)
+            .map(|(_, size)| *size)
+        else {
+            return;
+        };
+
+        let registry = world.resource::<TileRegistry>();
+        let commands: Vec<InsertTileColliderCommand> = tiles
+            .iter(world)
+            .filter(|(_, _, _, _, tilemap)| {
+                layers
+                    .get(world, tilemap.0)
+                    .map_or(false, |(layer, _)| *layer == Layer::World)
+            })
+            .filter_map(|(tile_entity, pos, id, flip, _)| {
+                let kind = registry.get(*id).filter(|kind| !kind.is_mergeable())?;
+                Some(InsertTileColliderCommand {
+                    tile_entity,
+                    pos: tpos_wpos(pos),
+                    properties: TileProperties {
+                        id: *id,
+                        flip: *flip,
+                    },
+                    kind: kind.clone(),
+                })
+            })
+            .collect();
+
+        for command in commands {
+            command.apply(world);
+        }
+
+        world
+            .get_resource_or_insert_with(TileColliders::default)
+            .mark_all_dirty(&size);
+    }
+}
+
+// Despawns all merged colliders and stops keeping them in sync
+pub struct RemoveMapCollidersCommand;
+
+impl Command for RemoveMapCollidersCommand {
+    fn apply(self, world: &mut World) {
+        if let Some(colliders) = world.remove_resource::<TileColliders>() {
+            for entity in colliders.chunks.into_values().flatten() {
+                world.despawn(entity);
+            }
+        }
+    }
+}
+
+pub fn sync_tile_colliders(
+    mut cmds: Commands,
+    mut tile_updates: EventReader<TileUpdateEvent>,
+    mut colliders: ResMut<TileColliders>,
+    tiles: Query<(&TileTextureIndex, &TileFlip)>,
+    registry: Res<TileRegistry>,
+) {
+    for ev in tile_updates.iter() {
+        if ev.layer != Layer::World {
+            continue;
+        }
+        // Blocks that were removed or replaced might still be part of a chunk collider
+        colliders.mark_dirty(&ev.pos);
+
+        match ev.modification {
+            TileModification::Added { new, .. } => {
+                let Ok((id, flip)) = tiles.get(new) else {
+                    continue;
+                };
+                let properties = TileProperties {
+                    id: *id,
+                    flip: *flip,
+                };
+                let Some(kind) = registry.get(properties.id) else {
+                    continue;
+                };
+                if kind.is_mergeable() {
+                    continue;
+                }
+
+                cmds.add(InsertTileColliderCommand {
+                    tile_entity: new,
+                    pos: tpos_wpos(&ev.pos),
+                    properties,
+                    kind: kind.clone(),
+                });
+            }
+            // Colliders live on the tile entity itself and get despawned together with it
+            TileModification::Removed { .. } => {}
+        }
+    }
+}
+
+pub fn rebuild_terrain_chunks(
+    mut cmds: Commands,
+    mut colliders: ResMut<TileColliders>,
+    storage: StorageAccess,
+) {
+    if colliders.dirty.is_empty() {
+        return;
+    }
+    let Some((_, size)) = storage.transform_size(Layer::World) else {
+        return;
+    };
+    let size = UVec2::new(size.x, size.y);
+
+    let dirty: Vec<UVec2> = colliders.dirty.drain().collect();
+    for chunk in dirty {
+        for entity in colliders.chunks.remove(&chunk).into_iter().flatten() {
+            cmds.entity(entity).despawn_recursive();
+        }
+
+        let min = chunk * CHUNK_SIZE;
+        // Chunk is outside of the map, e.g. after shrinking it
+        if min.x >= size.x || min.y >= size.y {
+            continue;
+        }
+        let chunk_size = (size - min).min(UVec2::splat(CHUNK_SIZE));
+
+        // Friction of every mergeable block, compared by bits so it can be used as a key
+        let friction = Grid::populate_from(chunk_size.as_ivec2(), |rpos| {
+            let pos = TilePos::from(min + rpos.as_uvec2());
+            storage
+                .get_kind(&pos, Layer::World)
+                .filter(|kind| kind.is_mergeable())
+                .map(|kind| kind.friction.map(Scalar::to_bits))
+        });
+        let mut groups: Vec<Option<u32>> = friction.iter().filter_map(|(_, f)| *f).collect();
+        groups.sort();
+        groups.dedup();
+
+        let mut entities = Vec::new();
+        for group in groups {
+            let solid =
+                Grid::populate_from(chunk_size.as_ivec2(), |rpos| friction[rpos] == Some(group));
+
+            let shapes: Vec<(Position, Rotation, Collider)> = greedy_rects(&solid)
+                .into_iter()
+                .map(|(rmin, rsize)| {
+                    // Offset from the center of the first tile in this chunk
+                    let center = (rmin.as_vec2() + (rsize.as_vec2() - 1.) / 2.) * 16.;
+                    let extents = rsize.as_vec2() * 16.;
+                    (
+                        Position(center),
+                        Rotation::default(),
+                        Collider::cuboid(extents.x, extents.y),
+                    )
+                })
+                .collect();
+
+            let mut entity = cmds.spawn((
+                TerrainChunk,
+                RigidBody::Static,
+                Collider::compound(shapes),
+                Position(tpos_wpos(&TilePos::from(min))),
+                Terrain,
+                Name::new("Terrain Chunk"),
+            ));
+            if let Some(friction) = group {
+                entity.insert(Friction::new(Scalar::from_bits(friction)));
+            }
+            entities.push(entity.id());
+        }
+        colliders.chunks.insert(chunk, entities);
+    }
+}
+
+// Covers all solid cells with as few rectangles as possible, returns (min, size) pairs
+pub fn greedy_rects(solid: &Grid<bool>) -> Vec<(IVec2, IVec2)> {
+    let size = solid.size();
+    let mut used = Grid::new(size, false);
+    let free = |used: &Grid<bool>, pos: IVec2| solid[pos] && !used[pos];
+
+    let mut rects = Vec::new();
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let min = IVec2::new(x, y);
+            if !free(&used, min) {
+                continue;
+            }
+
+            let mut width = 1;
+            while x + width < size.x && free(&used, IVec2::new(x + width, y)) {
+                width += 1;
+            }
+
+            let mut height = 1;
+            while y + height < size.y
+                && (0..width).all(|dx| free(&used, IVec2::new(x + dx, y + height)))
+            {
+                height += 1;
+            }
+
+            for dx in 0..width {
+                for dy in 0..height {
+                    used[IVec2::new(x + dx, y + dy)] = true;
+                }
+            }
+            rects.push((min, IVec2::new(width, height)));
+        }
+    }
+
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rows are listed top to bottom like they appear in the level
+    fn grid(rows: &[&str]) -> Grid<bool> {
+        let size = IVec2::new(rows[0].len() as i32, rows.len() as i32);
+        Grid::populate_from(size, |pos| {
+            rows[(size.y - 1 - pos.y) as usize].as_bytes()[pos.x as usize] == b'#'
+        })
+    }
+
+    fn covered(rects: &[(IVec2, IVec2)], size: IVec2) -> Grid<u32> {
+        let mut count = Grid::new(size, 0);
+        for (min, rect_size) in rects {
+            for x in min.x..min.x + rect_size.x {
+                for y in min.y..min.y + rect_size.y {
+                    count[IVec2::new(x, y)] += 1;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn empty_grid_has_no_rects() {
+        assert!(greedy_rects(&grid(&["...", "..."])).is_empty());
+    }
+
+    #[test]
+    fn full_grid_is_one_rect() {
+        let rects = greedy_rects(&grid(&["####", "####", "####"]));
+        assert_eq!(rects, vec![(IVec2::ZERO, IVec2::new(4, 3))]);
+    }
+
+    #[test]
+    fn rows_of_different_width_split() {
+        let rects = greedy_rects(&grid(&["##..", "####"]));
+        assert_eq!(
+            rects,
+            vec![
+                (IVec2::new(0, 0), IVec2::new(4, 1)),
+                (IVec2::new(0, 1), IVec2::new(2, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rects_cover_every_solid_cell_once() {
+        let solid = grid(&["#..##.#", "##.##..", "####.##", ".#..###"]);
+        let rects = greedy_rects(&solid);
+        let count = covered(&rects, solid.size());
+        for (pos, is_solid) in solid.iter() {
+            assert_eq!(count[pos], *is_solid as u32, "cell {pos}");
+        }
+    }
+}
@@ -3,7 +3,7 @@ use bevy::prelude::*;
 pub const ALL_LAYERS: [Layer; 3] = [Layer::World, Layer::Near, Layer::Far];
 
 #[repr(u8)]
-#[derive(Default, Component, Clone, Copy, Reflect, PartialEq)]
+#[derive(Default, Component, Clone, Copy, Debug, Reflect, PartialEq)]
 pub enum Layer {
     #[default]
     World,
@@ -1,18 +1,33 @@
 use anyhow::Context;
 use anyhow::Result;
 use bevy::{ecs::system::Command, prelude::*};
+use bevy_common_assets::ron::RonAssetPlugin;
 use bevy_ecs_tilemap::prelude::*;
+use bevy_xpbd_2d::prelude::{Gravity, PhysicsSet};
 use serde::{Deserialize, Serialize};
 
-use crate::{input::CursorPos, nono::Nonogram};
-
-use self::{layer::ALL_LAYERS, placement::TileUpdateEvent};
+use crate::{input::CursorPos, nono::Nonogram, phys::movement::MovementConfig};
+
+use self::{
+    collision::{rebuild_terrain_chunks, sync_tile_colliders, TileColliders},
+    layer::{Layer, ALL_LAYERS},
+    nav::{update_nav_graph, NavGraph},
+    placement::TileUpdateEvent,
+    tile::{load_tile_definitions, update_tile_registry, TileDefinitions, TileRegistry},
+    trigger::{
+        detect_hazards, detect_triggers, respawn_hurt_players, PlayerHurt, TriggerEvent,
+        TriggerZone,
+    },
+};
 use crate::level::serialization::TilePosRef;
 
+pub mod collision;
 pub mod layer;
+pub mod nav;
 pub mod placement;
 pub mod serialization;
 pub mod tile;
+pub mod trigger;
 
 pub struct LevelPlugin;
 
@@ -31,12 +46,48 @@ pub struct TilePosAnchor {
 #[derive(Resource, Default, Deref, DerefMut)]
 pub struct TileCursor(pub Option<TilePos>);
 
+// Tile the player is placed on when play testing the level
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct PlayerSpawn(pub Option<TilePos>);
+
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(TilemapPlugin);
+        app.add_plugins((
+            TilemapPlugin,
+            RonAssetPlugin::<TileDefinitions>::new(&["tiles.ron"]),
+        ));
         app.insert_resource(TileCursor::default());
-        app.add_systems(Update, update_tile_cursor);
-        app.add_event::<TileUpdateEvent>();
+        app.insert_resource(PlayerSpawn::default());
+        app.init_resource::<TileRegistry>();
+        app.init_resource::<NavGraph>();
+        app.add_systems(Startup, load_tile_definitions);
+        app.add_systems(Update, (update_tile_cursor, update_tile_registry));
+        app.add_event::<TileUpdateEvent>()
+            .add_event::<PlayerHurt>()
+            .add_event::<TriggerEvent>();
+        app.register_type::<TriggerZone>();
+        // Reacts to the contacts of the last physics step, in lockstep with it
+        app.add_systems(
+            FixedUpdate,
+            (
+                (detect_hazards, respawn_hurt_players).chain(),
+                detect_triggers,
+            )
+                .after(PhysicsSet::Sync),
+        );
+        app.add_systems(
+            PostUpdate,
+            (sync_tile_colliders, rebuild_terrain_chunks)
+                .chain()
+                .run_if(resource_exists::<TileColliders>()),
+        );
+        // Needs the movement config and gravity to know how far creatures can jump
+        app.add_systems(
+            PostUpdate,
+            update_nav_graph
+                .run_if(resource_exists::<MovementConfig>())
+                .run_if(resource_exists::<Gravity>()),
+        );
     }
 }
 
@@ -46,10 +97,13 @@ pub trait WorldMapExt: Sized {
 }
 
 impl WorldMapExt for &mut World {
+    // Every layer has its own tilemap, the world layer stands in for the whole map
     fn get_map(&mut self) -> Result<&TileStorage> {
-        let mut q = self.query::<&TileStorage>();
-        q.get_single(self)
-            .context("Failed to get single map entity")
+        let mut q = self.query::<(&TileStorage, &Layer)>();
+        q.iter(self)
+            .find(|(_, layer)| **layer == Layer::World)
+            .map(|(storage, _)| storage)
+            .context("Failed to get world layer map entity")
     }
 }
 
@@ -0,0 +1,681 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use bevy::{
+    prelude::*,
+    utils::hashbrown::{HashMap, HashSet},
+};
+use bevy_ecs_tilemap::prelude::*;
+use bevy_xpbd_2d::{
+    math::{Scalar, Vector},
+    prelude::*,
+};
+
+use crate::{
+    grid::Grid,
+    phys::{movement::MovementConfig, terrain::PoleType, TIMESTEP},
+};
+
+use super::{
+    layer::Layer,
+    placement::{StorageAccess, TileUpdateEvent},
+    tile::TileRegistry,
+};
+
+// Base cost of moving one tile, diagonal steps cost a bit more
+const STEP_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+const CLIMB_COST: u32 = 12;
+// Extra cost for leaving the ground so short detours are preferred over jumps
+const JUMP_COST: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavLink {
+    Walk,
+    Climb,
+    Jump,
+    Fall,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NavEdge {
+    pub to: IVec2,
+    pub link: NavLink,
+    pub cost: u32,
+}
+
+// Cell of a path and how it is reached from the previous one
+#[derive(Debug, Clone, Copy)]
+pub struct NavStep {
+    pub cell: IVec2,
+    pub link: NavLink,
+}
+
+// Planned path that is kept until the graph changes or either end leaves it
+#[derive(Debug, Clone, Default)]
+pub struct NavPath {
+    start: IVec2,
+    goal: IVec2,
+    generation: u32,
+    steps: VecDeque<NavStep>,
+}
+
+// What the navigation cares about of the tile in a cell
+#[derive(Debug, Default, Clone, Copy)]
+struct CellTile {
+    solid: bool,
+    slope: bool,
+    platform: bool,
+    hazard: bool,
+    pole: Option<PoleType>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct NavCell {
+    tile: CellTile,
+    edges: Vec<NavEdge>,
+}
+
+// How far a jump gets from standing still, derived from the movement config
+#[derive(Debug, Default, Clone, PartialEq)]
+struct JumpReach {
+    // Furthest horizontal distance in tiles, indexed by how many tiles the target is higher
+    distance: Vec<i32>,
+}
+
+impl JumpReach {
+    fn new(config: &MovementConfig, gravity: Scalar) -> Self {
+        // Step through the jump the same way the movement systems do it
+        let (mut t, mut y, mut vel) = (0., 0., config.jump_impulse);
+        let mut samples = Vec::new();
+        while t < 10. {
+            if t < config.jump_extension_time {
+                vel += config.jump_extension * TIMESTEP;
+            }
+            vel -= gravity * TIMESTEP;
+            y += vel * TIMESTEP;
+            t += TIMESTEP;
+            if vel < 0. && y < 0. {
+                break;
+            }
+            samples.push((t, y));
+        }
+
+        let peak = samples.iter().map(|(_, y)| *y).fold(0., Scalar::max);
+        let height = (peak / 16.).floor() as i32;
+        let distance = (0..=height)
+            .map(|dy| {
+                // Last moment the feet are still above the target
+                let landing = samples
+                    .iter()
+                    .rev()
+                    .find(|(_, y)| *y >= dy as Scalar * 16.)
+                    .map_or(0., |(t, _)| *t);
+                (config.max_speed * landing / 16.).floor() as i32
+            })
+            .collect();
+
+        Self { distance }
+    }
+
+    fn height(&self) -> i32 {
+        self.distance.len() as i32 - 1
+    }
+
+    fn max_distance(&self) -> i32 {
+        self.distance.iter().copied().max().unwrap_or(0)
+    }
+}
+
+// Navigation graph over the cells of the world layer, kept in sync with tile changes
+#[derive(Resource)]
+pub struct NavGraph {
+    cells: Grid<NavCell>,
+    reach: JumpReach,
+    // Columns whose tiles changed since the last update
+    dirty: HashSet<i32>,
+    // Bumped whenever edges change, invalidates planned paths
+    generation: u32,
+}
+
+impl Default for NavGraph {
+    fn default() -> Self {
+        Self {
+            cells: Grid::new(IVec2::ZERO, NavCell::default()),
+            reach: JumpReach::default(),
+            dirty: HashSet::new(),
+            generation: 0,
+        }
+    }
+}
+
+impl NavGraph {
+    pub fn cell_at(wpos: Vector) -> IVec2 {
+        (wpos / 16.).round().as_ivec2()
+    }
+
+    pub fn cell_center(cell: IVec2) -> Vector {
+        cell.as_vec2() * 16.
+    }
+
+    fn in_bounds(&self, pos: IVec2) -> bool {
+        let size = self.cells.size();
+        pos.x >= 0 && pos.y >= 0 && pos.x < size.x && pos.y < size.y
+    }
+
+    fn tile(&self, pos: IVec2) -> Option<CellTile> {
+        self.in_bounds(pos).then(|| self.cells[pos].tile)
+    }
+
+    // Can be moved through, platforms only block from above
+    fn passable(&self, pos: IVec2) -> bool {
+        self.tile(pos).map_or(false, |tile| !tile.solid)
+    }
+
+    pub fn walkable(&self, pos: IVec2) -> bool {
+        let Some(tile) = self.tile(pos).filter(|tile| !tile.solid && !tile.hazard) else {
+            return false;
+        };
+        tile.slope
+            || self
+                .tile(pos - IVec2::Y)
+                .map_or(false, |below| below.solid || below.platform)
+    }
+
+    pub fn climbable(&self, pos: IVec2) -> Option<PoleType> {
+        self.tile(pos)
+            .filter(|tile| !tile.hazard)
+            .and_then(|tile| tile.pole)
+    }
+
+    pub fn is_node(&self, pos: IVec2) -> bool {
+        self.walkable(pos) || self.climbable(pos).is_some()
+    }
+
+    pub fn edges(&self, pos: IVec2) -> &[NavEdge] {
+        if self.in_bounds(pos) {
+            &self.cells[pos].edges
+        } else {
+            &[]
+        }
+    }
+
+    pub fn iter_edges(&self) -> impl Iterator<Item = (IVec2, &NavEdge)> + '_ {
+        self.cells
+            .iter()
+            .flat_map(|(pos, cell)| cell.edges.iter().map(move |edge| (pos, edge)))
+    }
+
+    pub fn iter_nodes(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.cells
+            .iter()
+            .map(|(pos, _)| pos)
+            .filter(|pos| self.is_node(*pos))
+    }
+
+    // Bodies standing in a cell are often rounded into the one above or below it
+    pub fn nearest_node(&self, cell: IVec2) -> Option<IVec2> {
+        [cell, cell - IVec2::Y, cell + IVec2::Y, cell - IVec2::Y * 2]
+            .into_iter()
+            .find(|pos| self.is_node(*pos))
+    }
+
+    fn link_edges(&self, from: IVec2) -> Vec<NavEdge> {
+        let mut edges = Vec::new();
+        let mut add = |to: IVec2, link: NavLink, cost: u32| {
+            edges.push(NavEdge { to, link, cost });
+        };
+
+        if let Some(pole) = self.climbable(from) {
+            let runs_along = |pole: PoleType, dir: IVec2| match pole {
+                PoleType::Combined => true,
+                PoleType::Vertical => dir.x == 0,
+                PoleType::Horizontal => dir.y == 0,
+            };
+            for dir in [IVec2::Y, IVec2::NEG_Y, IVec2::X, IVec2::NEG_X] {
+                if !runs_along(pole, dir) {
+                    continue;
+                }
+                let to = from + dir;
+                let connected = self
+                    .climbable(to)
+                    .map_or(false, |other| runs_along(other, dir));
+                // Climbing off the top of a pole onto a platform or ledge
+                if connected || (dir == IVec2::Y && self.walkable(to)) {
+                    add(to, NavLink::Climb, CLIMB_COST);
+                }
+            }
+            // Letting go
+            if !self.walkable(from) {
+                if let Some(landing) = self.landing(from - IVec2::Y) {
+                    add(
+                        landing,
+                        NavLink::Fall,
+                        STEP_COST * (from.y - landing.y) as u32,
+                    );
+                }
+            }
+        }
+
+        if !self.walkable(from) {
+            return edges;
+        }
+
+        for dx in [-1, 1] {
+            let side = from + IVec2::X * dx;
+            if self.walkable(side) {
+                add(side, NavLink::Walk, STEP_COST);
+                continue;
+            }
+            // Slopes lead up or down diagonally
+            let on_slope = |pos: IVec2| self.tile(pos).map_or(false, |tile| tile.slope);
+            let up = side + IVec2::Y;
+            let down = side - IVec2::Y;
+            if self.walkable(up) && (on_slope(from) || on_slope(up)) {
+                add(up, NavLink::Walk, DIAGONAL_COST);
+                continue;
+            }
+            if self.walkable(down) && (on_slope(from) || on_slope(down)) {
+                add(down, NavLink::Walk, DIAGONAL_COST);
+                continue;
+            }
+            // Walking off the edge
+            if self.passable(side) {
+                if let Some(landing) = self.landing(side - IVec2::Y) {
+                    let cost = STEP_COST * (from.y - landing.y + 1) as u32;
+                    add(landing, NavLink::Fall, cost);
+                }
+            }
+        }
+
+        for dy in 0..=self.reach.height() {
+            let distance = self.reach.distance[dy as usize];
+            for dx in (-distance..=distance).filter(|dx| dx.abs() > 1 || dy > 0) {
+                let to = from + IVec2::new(dx, dy);
+                if self.walkable(to) && self.jump_clear(from, to) {
+                    let cost = STEP_COST * (dx.unsigned_abs() + dy as u32) + JUMP_COST;
+                    add(to, NavLink::Jump, cost);
+                }
+            }
+        }
+
+        edges
+    }
+
+    // First cell something falling from `pos` lands on
+    fn landing(&self, mut pos: IVec2) -> Option<IVec2> {
+        while self.passable(pos) {
+            if self.walkable(pos) {
+                return Some(pos);
+            }
+            pos -= IVec2::Y;
+        }
+        None
+    }
+
+    // Conservative check that rising straight up and then moving over to the target is free
+    fn jump_clear(&self, from: IVec2, to: IVec2) -> bool {
+        let top = to.y + 1;
+        let rise = (from.y..=top).all(|y| self.passable(IVec2::new(from.x, y)));
+        let (min, max) = (from.x.min(to.x), from.x.max(to.x));
+        let across = (min..=max).all(|x| self.passable(IVec2::new(x, top)));
+        rise && across && self.passable(to + IVec2::Y)
+    }
+
+    pub fn find_path(&self, start: IVec2, goal: IVec2) -> Option<Vec<NavStep>> {
+        if !self.is_node(start) || !self.is_node(goal) {
+            return None;
+        }
+        // Any edge costs at least a step per tile of distance along the larger axis
+        let heuristic = |pos: IVec2| {
+            let d = (goal - pos).abs();
+            STEP_COST * d.x.max(d.y) as u32
+        };
+
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<IVec2, NavStep> = HashMap::new();
+        let mut cost: HashMap<IVec2, u32> = HashMap::new();
+        cost.insert(start, 0);
+        open.push(Reverse((heuristic(start), start.x, start.y)));
+
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            let current = IVec2::new(x, y);
+            if current == goal {
+                let mut path = Vec::new();
+                let mut pos = goal;
+                while let Some(step) = came_from.get(&pos) {
+                    path.push(NavStep {
+                        cell: pos,
+                        link: step.link,
+                    });
+                    pos = step.cell;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let current_cost = cost[&current];
+            for edge in self.edges(current) {
+                let new_cost = current_cost + edge.cost;
+                if cost.get(&edge.to).map_or(true, |old| new_cost < *old) {
+                    cost.insert(edge.to, new_cost);
+                    came_from.insert(
+                        edge.to,
+                        NavStep {
+                            cell: current,
+                            link: edge.link,
+                        },
+                    );
+                    open.push(Reverse((
+                        new_cost + heuristic(edge.to),
+                        edge.to.x,
+                        edge.to.y,
+                    )));
+                }
+            }
+        }
+
+        None
+    }
+
+    // First step on the way between two world positions
+    pub fn next_step(&self, from: Vector, to: Vector) -> Option<NavStep> {
+        let start = self.nearest_node(Self::cell_at(from))?;
+        let goal = self.nearest_node(Self::cell_at(to))?;
+        self.find_path(start, goal)?.first().copied()
+    }
+
+    // Like next_step, but only plans again when the previous path no longer applies
+    pub fn follow(&self, path: &mut Option<NavPath>, from: Vector, to: Vector) -> Option<NavStep> {
+        let start = self.nearest_node(Self::cell_at(from))?;
+        let goal = self.nearest_node(Self::cell_at(to))?;
+
+        let valid = path
+            .as_ref()
+            .is_some_and(|path| path.goal == goal && path.generation == self.generation);
+        match path {
+            Some(path) if valid && path.start == start => {}
+            // Moved along the path, drop the steps that were taken
+            Some(path) if valid && path.steps.iter().any(|step| step.cell == start) => {
+                while let Some(step) = path.steps.pop_front() {
+                    if step.cell == start {
+                        break;
+                    }
+                }
+                path.start = start;
+            }
+            _ => {
+                *path = self.find_path(start, goal).map(|steps| NavPath {
+                    start,
+                    goal,
+                    generation: self.generation,
+                    steps: steps.into(),
+                });
+            }
+        }
+        path.as_ref()?.steps.front().copied()
+    }
+
+    fn mark_dirty(&mut self, column: i32) {
+        self.dirty.insert(column);
+    }
+}
+
+fn cell_tile(storage: &StorageAccess, pos: IVec2) -> CellTile {
+    let Some(kind) = storage.get_kind(&TilePos::from(pos.as_uvec2()), Layer::World) else {
+        return CellTile::default();
+    };
+    CellTile {
+        solid: kind.is_solid(),
+        slope: kind.is_slope(),
+        platform: kind.is_platform(),
+        hazard: kind.is_hazard(),
+        pole: kind.is_pole(),
+    }
+}
+
+pub fn update_nav_graph(
+    mut nav: ResMut<NavGraph>,
+    mut tile_updates: EventReader<TileUpdateEvent>,
+    storage: StorageAccess,
+    registry: Res<TileRegistry>,
+    config: Res<MovementConfig>,
+    gravity: Res<Gravity>,
+) {
+    for ev in tile_updates.iter() {
+        if ev.layer == Layer::World {
+            nav.mark_dirty(ev.pos.x as i32);
+        }
+    }
+
+    // Simulating the jump is only worth it when the tuning changed
+    let mut reach_changed = false;
+    if config.is_changed() || gravity.is_changed() {
+        let reach = JumpReach::new(&config, gravity.0.length());
+        if nav.reach != reach {
+            nav.reach = reach;
+            reach_changed = true;
+        }
+    }
+
+    let Some((_, size)) = storage.transform_size(Layer::World) else {
+        if nav.cells.size() != IVec2::ZERO {
+            let generation = nav.generation + 1;
+            let reach = std::mem::take(&mut nav.reach);
+            *nav = NavGraph {
+                reach,
+                generation,
+                ..default()
+            };
+        }
+        return;
+    };
+    let size = IVec2::new(size.x as i32, size.y as i32);
+
+    // Anything that changes every cell or edge at once needs a full rebuild
+    if nav.cells.size() != size || reach_changed || registry.is_changed() {
+        nav.cells = Grid::new(size, NavCell::default());
+        nav.dirty = (0..size.x).collect();
+    }
+    if nav.dirty.is_empty() {
+        return;
+    }
+
+    let dirty: Vec<i32> = nav
+        .dirty
+        .drain()
+        .filter(|x| (0..size.x).contains(x))
+        .collect();
+    for x in dirty.iter() {
+        for y in 0..size.y {
+            let pos = IVec2::new(*x, y);
+            nav.cells[pos].tile = cell_tile(&storage, pos);
+        }
+    }
+
+    // Edges can lead as far as a jump reaches, falls can go down any distance
+    let spread = nav.reach.max_distance() + 1;
+    let columns: HashSet<i32> = dirty
+        .iter()
+        .flat_map(|x| (x - spread)..=(x + spread))
+        .filter(|x| (0..size.x).contains(x))
+        .collect();
+    for x in columns {
+        for y in 0..size.y {
+            let pos = IVec2::new(x, y);
+            let edges = nav.link_edges(pos);
+            nav.cells[pos].edges = edges;
+        }
+    }
+    nav.generation = nav.generation.wrapping_add(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rows are listed top to bottom like they appear in the level,
+    // '#' solid, '-' platform, '^' hazard, '|' vertical pole
+    fn graph(rows: &[&str], reach: &[i32]) -> NavGraph {
+        let size = IVec2::new(rows[0].len() as i32, rows.len() as i32);
+        let cells = Grid::populate_from(size, |pos| {
+            let tile = match rows[(size.y - 1 - pos.y) as usize].as_bytes()[pos.x as usize] {
+                b'#' => CellTile {
+                    solid: true,
+                    ..default()
+                },
+                b'-' => CellTile {
+                    platform: true,
+                    ..default()
+                },
+                b'^' => CellTile {
+                    hazard: true,
+                    ..default()
+                },
+                b'|' => CellTile {
+                    pole: Some(PoleType::Vertical),
+                    ..default()
+                },
+                _ => CellTile::default(),
+            };
+            NavCell {
+                tile,
+                edges: Vec::new(),
+            }
+        });
+        let mut nav = NavGraph {
+            cells,
+            reach: JumpReach {
+                distance: reach.to_vec(),
+            },
+            ..default()
+        };
+        let nodes: Vec<IVec2> = nav.cells.iter().map(|(pos, _)| pos).collect();
+        for pos in nodes {
+            let edges = nav.link_edges(pos);
+            nav.cells[pos].edges = edges;
+        }
+        nav
+    }
+
+    fn links(path: &[NavStep]) -> Vec<NavLink> {
+        path.iter().map(|step| step.link).collect()
+    }
+
+    #[test]
+    fn walks_along_the_floor() {
+        let nav = graph(&["......", "......", "######"], &[0]);
+        let path = nav.find_path(IVec2::new(0, 1), IVec2::new(5, 1)).unwrap();
+        assert_eq!(links(&path), vec![NavLink::Walk; 5]);
+        assert_eq!(path.last().unwrap().cell, IVec2::new(5, 1));
+    }
+
+    #[test]
+    fn no_path_to_cells_that_can_not_be_stood_in() {
+        let nav = graph(&["......", "......", "######"], &[0]);
+        assert!(nav.find_path(IVec2::new(0, 1), IVec2::new(3, 2)).is_none());
+        assert!(nav.find_path(IVec2::new(0, 1), IVec2::new(3, 0)).is_none());
+    }
+
+    #[test]
+    fn drops_off_ledges_but_can_not_get_back_up_without_jumping() {
+        let rows = [".......", ".......", "###....", "###....", "#######"];
+        let nav = graph(&rows, &[0]);
+        let (top, bottom) = (IVec2::new(1, 3), IVec2::new(5, 1));
+
+        let path = nav.find_path(top, bottom).unwrap();
+        assert!(links(&path).contains(&NavLink::Fall));
+        assert!(!links(&path).contains(&NavLink::Jump));
+        assert!(nav.find_path(bottom, top).is_none());
+    }
+
+    #[test]
+    fn jumps_up_ledges_within_reach() {
+        let rows = [".......", ".......", "###....", "###....", "#######"];
+        let (top, bottom) = (IVec2::new(1, 3), IVec2::new(5, 1));
+
+        let path = graph(&rows, &[3, 3, 2]).find_path(bottom, top).unwrap();
+        assert!(links(&path).contains(&NavLink::Jump));
+        // Only one tile high jumps
+        assert!(graph(&rows, &[3, 3]).find_path(bottom, top).is_none());
+    }
+
+    #[test]
+    fn jumps_over_gaps_within_reach() {
+        let rows = ["..........", "..........", "###....###"];
+        let (start, goal) = (IVec2::new(1, 1), IVec2::new(8, 1));
+
+        let path = graph(&rows, &[5]).find_path(start, goal).unwrap();
+        assert_eq!(
+            links(&path),
+            vec![NavLink::Walk, NavLink::Jump, NavLink::Walk]
+        );
+        assert!(graph(&rows, &[4]).find_path(start, goal).is_none());
+    }
+
+    #[test]
+    fn jumps_over_hazards_instead_of_walking_into_them() {
+        let rows = [".....", ".....", "..^..", "#####"];
+        let (start, goal) = (IVec2::new(0, 1), IVec2::new(4, 1));
+
+        assert!(graph(&rows, &[1]).find_path(start, goal).is_none());
+        let path = graph(&rows, &[2]).find_path(start, goal).unwrap();
+        assert!(path.iter().all(|step| step.cell != IVec2::new(2, 1)));
+        assert!(links(&path).contains(&NavLink::Jump));
+    }
+
+    #[test]
+    fn jumps_are_blocked_by_ceilings() {
+        let rows = [".......", "#######", ".......", "###....", "#######"];
+        // The ceiling leaves no room to rise above the ledge
+        let nav = graph(&rows, &[3, 3, 2]);
+        assert!(nav.find_path(IVec2::new(5, 1), IVec2::new(1, 2)).is_none());
+    }
+
+    #[test]
+    fn climbs_poles() {
+        let rows = ["......", "...|..", "...|..", "...|..", "######"];
+        let nav = graph(&rows, &[0]);
+        let path = nav.find_path(IVec2::new(0, 1), IVec2::new(3, 3)).unwrap();
+        assert_eq!(
+            links(&path),
+            vec![
+                NavLink::Walk,
+                NavLink::Walk,
+                NavLink::Walk,
+                NavLink::Climb,
+                NavLink::Climb
+            ]
+        );
+        // Letting go drops back down to the floor
+        let path = nav.find_path(IVec2::new(3, 3), IVec2::new(5, 1)).unwrap();
+        assert_eq!(path[0].link, NavLink::Fall);
+    }
+
+    #[test]
+    fn follow_keeps_the_path_while_moving_along_it() {
+        let nav = graph(&["......", "......", "######"], &[0]);
+        let mut path = None;
+        let goal = NavGraph::cell_center(IVec2::new(5, 1));
+
+        let step = nav.follow(&mut path, NavGraph::cell_center(IVec2::new(0, 1)), goal);
+        assert_eq!(step.unwrap().cell, IVec2::new(1, 1));
+        let step = nav.follow(&mut path, NavGraph::cell_center(IVec2::new(2, 1)), goal);
+        assert_eq!(step.unwrap().cell, IVec2::new(3, 1));
+        assert_eq!(path.as_ref().unwrap().steps.len(), 3);
+    }
+
+    #[test]
+    fn jump_reach_shrinks_with_height() {
+        let config = MovementConfig::default();
+        let reach = JumpReach::new(&config, 200.);
+        assert!(reach.height() > 0);
+        assert!(reach.distance.windows(2).all(|pair| pair[0] >= pair[1]));
+
+        let heavy = JumpReach::new(&config, 400.);
+        assert!(heavy.height() <= reach.height());
+        assert!(heavy.max_distance() <= reach.max_distance());
+    }
+}
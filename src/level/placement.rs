@@ -1,7 +1,11 @@
 use bevy::{ecs::system::SystemParam, prelude::*};
 use bevy_ecs_tilemap::prelude::*;
 
-use super::{layer::Layer, tile::TileKind};
+use super::{
+    layer::{Layer, ALL_LAYERS},
+    tile::{TileKind, TileRegistry},
+    trigger::TriggerZone,
+};
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct TileProperties {
@@ -14,8 +18,104 @@ pub enum TileModification {
     Removed { old: Entity },
 }
 
+// Which part of the map stays fixed when resizing
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    #[default]
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+pub const ALL_ANCHORS: [Anchor; 9] = [
+    Anchor::TopLeft,
+    Anchor::Top,
+    Anchor::TopRight,
+    Anchor::Left,
+    Anchor::Center,
+    Anchor::Right,
+    Anchor::BottomLeft,
+    Anchor::Bottom,
+    Anchor::BottomRight,
+];
+
+impl Anchor {
+    // How far tiles move when the map changes from old to new size
+    pub fn offset(&self, old: UVec2, new: UVec2) -> IVec2 {
+        use Anchor::*;
+        let diff = new.as_ivec2() - old.as_ivec2();
+        // Measured in halves of the size difference
+        let (x, y) = match self {
+            TopLeft => (0, 2),
+            Top => (1, 2),
+            TopRight => (2, 2),
+            Left => (0, 1),
+            Center => (1, 1),
+            Right => (2, 1),
+            BottomLeft => (0, 0),
+            Bottom => (1, 0),
+            BottomRight => (2, 0),
+        };
+        IVec2::new(diff.x * x / 2, diff.y * y / 2)
+    }
+
+    // Where a tile ends up after resizing, none if it gets cropped
+    pub fn shift(&self, pos: &TilePos, old: UVec2, new: UVec2) -> Option<TilePos> {
+        let offset = self.offset(old, new);
+        TilePos::from_i32_pair(
+            pos.x as i32 + offset.x,
+            pos.y as i32 + offset.y,
+            &TilemapSize::from(new),
+        )
+    }
+
+    pub fn name(&self) -> &str {
+        use Anchor::*;
+        match self {
+            TopLeft => "Top Left",
+            Top => "Top",
+            TopRight => "Top Right",
+            Left => "Left",
+            Center => "Center",
+            Right => "Right",
+            BottomLeft => "Bottom Left",
+            Bottom => "Bottom",
+            BottomRight => "Bottom Right",
+        }
+    }
+}
+
+// Number of tiles that would end up outside of the map after resizing
+pub fn count_cropped(
+    storage: &TileStorage,
+    old_size: &TilemapSize,
+    new_size: UVec2,
+    anchor: Anchor,
+) -> usize {
+    let old = UVec2::new(old_size.x, old_size.y);
+    let offset = anchor.offset(old, new_size);
+    let new_size = TilemapSize::from(new_size);
+    storage
+        .iter()
+        .enumerate()
+        .filter(|(idx, tile)| {
+            let x = (*idx as u32 % old.x) as i32 + offset.x;
+            let y = (*idx as u32 / old.x) as i32 + offset.y;
+            tile.is_some() && TilePos::from_i32_pair(x, y, &new_size).is_none()
+        })
+        .count()
+}
+
 #[derive(Event)]
 pub struct TileUpdateEvent {
+    pub pos: TilePos,
+    pub layer: Layer,
     pub modification: TileModification,
 }
 
@@ -23,9 +123,11 @@ pub struct TileUpdateEvent {
 pub struct StorageAccess<'w, 's> {
     cmds: Commands<'w, 's>,
     layers: Query<'w, 's, (Entity, &'static mut TileStorage, &'static Layer)>,
-    transforms: Query<'w, 's, (&'static Transform, &'static TilemapSize, &'static Layer)>,
+    transforms: Query<'w, 's, (&'static Transform, &'static mut TilemapSize, &'static Layer)>,
     tile_properties: Query<'w, 's, (&'static TileTextureIndex, &'static TileFlip)>,
+    triggers: Query<'w, 's, &'static TriggerZone>,
     tile_update_event_writer: EventWriter<'w, TileUpdateEvent>,
+    registry: Res<'w, TileRegistry>,
 }
 
 impl<'w, 's> StorageAccess<'w, 's> {
@@ -42,10 +144,14 @@ impl<'w, 's> StorageAccess<'w, 's> {
             Layer::Near => Color::rgba_u8(0, 127, 0, 127),
             Layer::Far => Color::rgba_u8(127, 0, 0, 63),
         };
+        let name = self
+            .registry
+            .get(tile_properties.id)
+            .map_or("Unknown Tile".to_owned(), |kind| kind.name());
         let tile_entity = self
             .cmds
             .spawn((
-                Name::new(TileKind::from(tile_properties.id).name()),
+                Name::new(name),
                 TileBundle {
                     position: *pos,
                     tilemap_id: TilemapId(tilemap_entity),
@@ -72,6 +178,8 @@ impl<'w, 's> StorageAccess<'w, 's> {
         };
         if let Some(new) = self.set_unchecked(pos, tile_properties, layer) {
             self.tile_update_event_writer.send(TileUpdateEvent {
+                pos: *pos,
+                layer,
                 modification: TileModification::Added { old: None, new },
             });
         }
@@ -84,6 +192,8 @@ impl<'w, 's> StorageAccess<'w, 's> {
         }
         if let Some(new) = self.set_unchecked(pos, id, layer) {
             self.tile_update_event_writer.send(TileUpdateEvent {
+                pos: *pos,
+                layer,
                 modification: TileModification::Added { old, new },
             });
         }
@@ -100,11 +210,20 @@ impl<'w, 's> StorageAccess<'w, 's> {
     pub fn remove(&mut self, pos: &TilePos, layer: Layer) {
         if let Some(old) = self.despawn(pos, layer) {
             self.tile_update_event_writer.send(TileUpdateEvent {
+                pos: *pos,
+                layer,
                 modification: TileModification::Removed { old },
             });
         }
     }
 
+    // Adds extra components to an existing tile
+    pub fn insert(&mut self, pos: &TilePos, layer: Layer, bundle: impl Bundle) {
+        if let Some(entity) = self.get(pos, layer) {
+            self.cmds.entity(entity).insert(bundle);
+        }
+    }
+
     pub fn get(&self, pos: &TilePos, layer: Layer) -> Option<Entity> {
         let storage = self.storage(layer)?;
         storage.get(pos)
@@ -119,6 +238,15 @@ impl<'w, 's> StorageAccess<'w, 's> {
         })
     }
 
+    pub fn get_kind(&self, pos: &TilePos, layer: Layer) -> Option<&TileKind> {
+        let properties = self.get_properties(pos, layer)?;
+        self.registry.get(properties.id)
+    }
+
+    pub fn registry(&self) -> &TileRegistry {
+        &self.registry
+    }
+
     pub fn transform_size(&self, layer: Layer) -> Option<(&Transform, &TilemapSize)> {
         let res = self.transforms.iter().find(|(_, _, l)| **l == layer)?;
         Some((res.0, res.1))
@@ -147,14 +275,154 @@ impl<'w, 's> StorageAccess<'w, 's> {
         };
         let mut pos_to_remove = Vec::new();
         storage.iter_mut().enumerate().for_each(|(idx, tile)| {
-            pos_to_remove.push(TilePos {
+            let pos = TilePos {
                 x: idx as u32 % size.x,
                 y: idx as u32 / size.x,
-            });
+            };
+            pos_to_remove.push(pos);
             if let Some(tile) = tile {
                 self.cmds.entity(*tile).despawn_recursive();
+                // Colliders and the navigation graph have to learn about cleared tiles too
+                self.tile_update_event_writer.send(TileUpdateEvent {
+                    pos,
+                    layer,
+                    modification: TileModification::Removed { old: *tile },
+                });
             }
         });
         pos_to_remove.iter().for_each(|pos| storage.remove(&pos));
     }
+
+    pub fn tiles(&self, layer: Layer) -> Vec<(TilePos, TileProperties)> {
+        let (Some(storage), Some((_, size))) = (self.storage(layer), self.transform_size(layer))
+        else {
+            return Vec::new();
+        };
+        storage
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.is_some())
+            .filter_map(|(idx, _)| {
+                let pos = TilePos {
+                    x: idx as u32 % size.x,
+                    y: idx as u32 / size.x,
+                };
+                Some((pos, self.get_properties(&pos, layer)?))
+            })
+            .collect()
+    }
+
+    // Grows or shrinks every layer keeping the anchored part of the map in place
+    pub fn resize(&mut self, new_size: UVec2, anchor: Anchor) {
+        for layer in ALL_LAYERS.iter() {
+            self.resize_layer(*layer, new_size, anchor);
+        }
+    }
+
+    fn resize_layer(&mut self, layer: Layer, new_size: UVec2, anchor: Anchor) {
+        let Some((_, old_size)) = self.transform_size(layer) else {
+            return;
+        };
+        let old_size = UVec2::new(old_size.x, old_size.y);
+
+        // Trigger zones live on the tile entities, which get recreated
+        let tiles: Vec<(TilePos, TileProperties, Option<TriggerZone>)> = self
+            .tiles(layer)
+            .into_iter()
+            .map(|(pos, properties)| {
+                let zone = self
+                    .get(&pos, layer)
+                    .and_then(|tile| self.triggers.get(tile).ok())
+                    .cloned();
+                (pos, properties, zone)
+            })
+            .collect();
+
+        tiles.iter().for_each(|(pos, _, _)| self.remove(pos, layer));
+
+        if let Some((_, mut storage, _)) = self.layers.iter_mut().find(|(_, _, l)| **l == layer) {
+            *storage = TileStorage::empty(TilemapSize::from(new_size));
+        }
+        if let Some((_, mut size, _)) = self.transforms.iter_mut().find(|(_, _, l)| **l == layer) {
+            *size = TilemapSize::from(new_size);
+        }
+
+        for (pos, properties, zone) in tiles {
+            // Cropped
+            let Some(new_pos) = anchor.shift(&pos, old_size, new_size) else {
+                continue;
+            };
+            self.replace(&new_pos, properties, layer);
+            if let Some(zone) = zone {
+                self.insert(&new_pos, layer, zone);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage(size: UVec2, tiles: &[(u32, u32)]) -> (TileStorage, TilemapSize) {
+        let size = TilemapSize::from(size);
+        let mut storage = TileStorage::empty(size);
+        for (idx, (x, y)) in tiles.iter().enumerate() {
+            storage.set(&TilePos::new(*x, *y), Entity::from_raw(idx as u32));
+        }
+        (storage, size)
+    }
+
+    #[test]
+    fn offset_keeps_anchored_side_in_place() {
+        let old = UVec2::new(4, 4);
+        let new = UVec2::new(8, 6);
+        assert_eq!(Anchor::BottomLeft.offset(old, new), IVec2::new(0, 0));
+        assert_eq!(Anchor::Center.offset(old, new), IVec2::new(2, 1));
+        assert_eq!(Anchor::TopRight.offset(old, new), IVec2::new(4, 2));
+        assert_eq!(Anchor::Top.offset(old, new), IVec2::new(2, 2));
+        assert_eq!(Anchor::Left.offset(old, new), IVec2::new(0, 1));
+    }
+
+    #[test]
+    fn offset_shrinking_moves_tiles_back() {
+        let old = UVec2::new(8, 6);
+        let new = UVec2::new(4, 4);
+        assert_eq!(Anchor::BottomLeft.offset(old, new), IVec2::new(0, 0));
+        assert_eq!(Anchor::Center.offset(old, new), IVec2::new(-2, -1));
+        assert_eq!(Anchor::TopRight.offset(old, new), IVec2::new(-4, -2));
+    }
+
+    #[test]
+    fn offset_is_zero_without_resize() {
+        let size = UVec2::new(5, 3);
+        for anchor in ALL_ANCHORS {
+            assert_eq!(anchor.offset(size, size), IVec2::ZERO);
+        }
+    }
+
+    #[test]
+    fn growing_crops_nothing() {
+        let (storage, size) = storage(UVec2::new(3, 3), &[(0, 0), (2, 2), (1, 2)]);
+        for anchor in ALL_ANCHORS {
+            assert_eq!(count_cropped(&storage, &size, UVec2::new(5, 4), anchor), 0);
+        }
+    }
+
+    #[test]
+    fn shrinking_crops_tiles_on_the_far_side() {
+        let (storage, size) = storage(UVec2::new(4, 4), &[(0, 0), (3, 0), (0, 3), (3, 3), (1, 1)]);
+        let new = UVec2::new(2, 2);
+        // Keeps the bottom left quarter with (0, 0) and (1, 1)
+        assert_eq!(count_cropped(&storage, &size, new, Anchor::BottomLeft), 3);
+        // Keeps the top right quarter
+        assert_eq!(count_cropped(&storage, &size, new, Anchor::TopRight), 4);
+        // Keeps the middle, only (1, 1) survives
+        assert_eq!(count_cropped(&storage, &size, new, Anchor::Center), 4);
+        // Keeps the bottom two rows
+        assert_eq!(
+            count_cropped(&storage, &size, UVec2::new(4, 2), Anchor::Bottom),
+            2
+        );
+    }
 }
@@ -6,7 +6,9 @@ use serde::{Deserialize, Serialize};
 
 use super::{
     layer::Layer,
-    placement::{StorageAccess, TileProperties},
+    placement::{Anchor, StorageAccess, TileProperties},
+    trigger::TriggerZone,
+    PlayerSpawn,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -38,13 +40,26 @@ pub struct SerializableTile {
     pub flip: TileFlip,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SerializableTrigger {
+    #[serde(with = "TilePosRef")]
+    pub pos: TilePos,
+    pub name: String,
+}
+
 fn is_default_flip(flip: &TileFlip) -> bool {
     !flip.x && !flip.y && !flip.d
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SerializableLevel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<UVec2>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<UVec2>,
     pub tiles: Vec<SerializableTile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<SerializableTrigger>,
 }
 
 #[derive(SystemParam)]
@@ -58,7 +73,9 @@ pub struct LevelSerializer<'w, 's> {
             &'static TileFlip,
         ),
     >,
+    triggers: Query<'w, 's, (&'static TilePos, &'static TriggerZone)>,
     pub storage_access: StorageAccess<'w, 's>,
+    pub spawn: ResMut<'w, PlayerSpawn>,
 }
 
 impl<'w, 's> LevelSerializer<'w, 's> {
@@ -74,7 +91,28 @@ impl<'w, 's> LevelSerializer<'w, 's> {
             });
         }
 
-        Some(SerializableLevel { tiles })
+        let size = self
+            .storage_access
+            .transform_size(Layer::World)
+            .map(|(_, size)| UVec2::new(size.x, size.y));
+
+        let spawn = (**self.spawn).map(UVec2::from);
+
+        let triggers = self
+            .triggers
+            .iter()
+            .map(|(pos, zone)| SerializableTrigger {
+                pos: *pos,
+                name: zone.0.clone(),
+            })
+            .collect();
+
+        Some(SerializableLevel {
+            size,
+            spawn,
+            tiles,
+            triggers,
+        })
     }
 
     pub fn save_to_file(&self, path: PathBuf) {
@@ -89,18 +127,99 @@ impl<'w, 's> LevelSerializer<'w, 's> {
     pub fn load_from_file(&mut self, path: PathBuf) {
         if let Some(data) = fs::read_to_string(path).ok() {
             if let Some(level) = ron::from_str::<SerializableLevel>(&data).ok() {
-                self.storage_access.clear(Layer::World);
-                for tile in level.tiles {
-                    self.storage_access.replace(
-                        &tile.pos,
-                        TileProperties {
-                            id: tile.id,
-                            flip: tile.flip,
-                        },
-                        Layer::World,
-                    );
-                }
+                self.load(level);
             }
         }
     }
+
+    pub fn load(&mut self, level: SerializableLevel) {
+        self.storage_access.clear(Layer::World);
+        **self.spawn = level.spawn.map(TilePos::from);
+        if let Some(size) = level.size {
+            self.storage_access.resize(size, Anchor::BottomLeft);
+        }
+        for tile in level.tiles {
+            self.storage_access.replace(
+                &tile.pos,
+                TileProperties {
+                    id: tile.id,
+                    flip: tile.flip,
+                },
+                Layer::World,
+            );
+        }
+        for trigger in level.triggers {
+            self.storage_access
+                .insert(&trigger.pos, Layer::World, TriggerZone(trigger.name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use bevy_ecs_tilemap::prelude::*;
+
+    use crate::level::{layer::ALL_LAYERS, placement::TileUpdateEvent, tile::TileRegistry};
+
+    use super::*;
+
+    fn empty_level(size: UVec2) -> World {
+        let mut world = World::new();
+        world.init_resource::<TileRegistry>();
+        world.init_resource::<PlayerSpawn>();
+        world.init_resource::<Events<TileUpdateEvent>>();
+        for layer in ALL_LAYERS {
+            world.spawn((
+                TileStorage::empty(TilemapSize::from(size)),
+                TilemapSize::from(size),
+                Transform::default(),
+                layer,
+            ));
+        }
+        world
+    }
+
+    fn triggers(world: &mut World) -> Vec<(TilePos, Layer, String)> {
+        let mut tiles = world.query::<(&TilePos, &TilemapId, &TriggerZone)>();
+        let mut layers = world.query::<&Layer>();
+        let mut triggers: Vec<_> = tiles
+            .iter(world)
+            .map(|(pos, map, zone)| (*pos, *layers.get(world, map.0).unwrap(), zone.0.clone()))
+            .collect();
+        triggers.sort_by_key(|(pos, _, _)| (pos.x, pos.y));
+        triggers
+    }
+
+    #[test]
+    fn triggers_survive_saving_and_loading() {
+        let size = UVec2::new(4, 4);
+        let mut world = empty_level(size);
+        let mut state = SystemState::<LevelSerializer>::new(&mut world);
+
+        let mut serializer = state.get_mut(&mut world);
+        for (pos, name) in [(TilePos::new(1, 0), "door"), (TilePos::new(2, 3), "exit")] {
+            let properties = TileProperties {
+                id: TileTextureIndex(7),
+                flip: TileFlip::default(),
+            };
+            let access = &mut serializer.storage_access;
+            access.replace(&pos, properties, Layer::World);
+            access.insert(&pos, Layer::World, TriggerZone(name.to_owned()));
+        }
+        state.apply(&mut world);
+        let before = triggers(&mut world);
+        assert_eq!(before.len(), 2);
+
+        let level = state.get_mut(&mut world).save().unwrap();
+        let ron = ron::to_string(&level).unwrap();
+        let level = ron::from_str::<SerializableLevel>(&ron).unwrap();
+
+        let mut loaded = empty_level(size);
+        let mut state = SystemState::<LevelSerializer>::new(&mut loaded);
+        state.get_mut(&mut loaded).load(level);
+        state.apply(&mut loaded);
+
+        assert_eq!(triggers(&mut loaded), before);
+    }
 }
@@ -1,133 +1,198 @@
 use bevy::ecs::system::Command;
-use bevy::prelude::Entity;
-use bevy::prelude::Vec2;
-use bevy_ecs_tilemap::tiles::TileTextureIndex;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy_ecs_tilemap::tiles::{TileFlip, TileTextureIndex};
 use bevy_xpbd_2d::math::*;
 use bevy_xpbd_2d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::phys::terrain::Platform;
 use crate::phys::terrain::Pole;
 use crate::phys::terrain::PoleType;
 use crate::phys::terrain::Terrain;
+use crate::phys::water::Water;
 
 use super::placement::TileProperties;
+use super::trigger::Hazard;
 
-pub enum TileKind {
-    Square,
-    Slope,
-    Pole(PoleType),
-    Platform,
+pub const TILE_DEFINITIONS_PATH: &str = "tiles/default.tiles.ron";
+
+// Collider shapes of a tile, sizes are in pixels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TileShape {
+    Cuboid(Vec2),
+    // Legs of the triangle lie on the tile edges, flipping the tile mirrors it
+    RightTriangle(Scalar),
+    Compound(Vec<(Vec2, TileShape)>),
 }
 
-impl TileKind {
-    pub fn is_pole(&self) -> Option<PoleType> {
-        use TileKind::*;
+impl TileShape {
+    pub fn collider(&self, flip: TileFlip) -> Collider {
+        use TileShape::*;
         match self {
-            Pole(kind) => Some(*kind),
-            _ => None,
+            Cuboid(size) => Collider::cuboid(size.x, size.y),
+            RightTriangle(size) => {
+                let dir = match (flip.x, flip.y) {
+                    (false, false) => Vector::new(1., 1.),
+                    (true, false) => Vector::new(-1., 1.),
+                    (false, true) => Vector::new(1., -1.),
+                    (true, true) => Vector::new(-1., -1.),
+                };
+                let corner = Vector::splat(-size / 2.) * dir;
+                Collider::triangle(
+                    corner + Vector::X * *size * dir.x,
+                    corner + Vector::Y * *size * dir.y,
+                    corner,
+                )
+            }
+            Compound(shapes) => Collider::compound(
+                shapes
+                    .iter()
+                    .map(|(offset, shape)| {
+                        (Position(*offset), Rotation::default(), shape.collider(flip))
+                    })
+                    .collect(),
+            ),
         }
     }
+}
+
+// Describes how a tile behaves, loaded from the tile definitions asset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TileKind {
+    // Also the index into the tile texture
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub collider: Option<TileShape>,
+    #[serde(default)]
+    pub offset: Vec2,
+    #[serde(default)]
+    pub solid: bool,
+    #[serde(default)]
+    pub slope: bool,
+    #[serde(default)]
+    pub pole: Option<PoleType>,
+    #[serde(default)]
+    pub platform: bool,
+    #[serde(default)]
+    pub sensor: bool,
+    #[serde(default)]
+    pub friction: Option<Scalar>,
+    #[serde(default)]
+    pub hazard: bool,
+    // Painted tiles get a named trigger zone
+    #[serde(default)]
+    pub trigger: bool,
+    #[serde(default)]
+    pub water: bool,
+}
+
+impl TileKind {
+    pub fn is_pole(&self) -> Option<PoleType> {
+        self.pole
+    }
 
     pub fn is_platform(&self) -> bool {
-        matches!(self, TileKind::Platform)
+        self.platform
     }
 
     pub fn is_solid(&self) -> bool {
-        matches!(self, TileKind::Square)
+        self.solid
+    }
+
+    // Full blocks without any special properties can be merged into chunk colliders
+    pub fn is_mergeable(&self) -> bool {
+        let full_block = matches!(
+            self.collider,
+            Some(TileShape::Cuboid(size)) if size == Vec2::splat(16.)
+        );
+        self.solid && full_block && !self.sensor && self.offset == Vec2::ZERO
     }
 
     pub fn is_slope(&self) -> bool {
-        matches!(self, TileKind::Slope)
+        self.slope
+    }
+
+    pub fn is_hazard(&self) -> bool {
+        self.hazard
+    }
+
+    pub fn is_trigger(&self) -> bool {
+        self.trigger
+    }
+
+    pub fn is_water(&self) -> bool {
+        self.water
     }
 
     pub fn offset(&self) -> Vector {
-        use TileKind::*;
-        match self {
-            Platform => Vector::Y * 5.,
-            _ => Vector::ZERO,
-        }
+        self.offset
     }
 
     pub fn name(&self) -> String {
-        use TileKind::*;
-        match self {
-            Square => "Square".to_owned(),
-            Slope => "Slope".to_owned(),
-            Pole(_) => "Pole".to_owned(),
-            Platform => "Platform".to_owned(),
-        }
+        self.name.clone()
     }
 }
 
-impl Into<TileTextureIndex> for TileKind {
-    fn into(self) -> TileTextureIndex {
-        use TileKind::*;
-        let id = match self {
-            Square => 0,
-            Slope => 1,
-            Pole(kind) => match kind {
-                PoleType::Vertical => 2,
-                PoleType::Horizontal => 3,
-                PoleType::Combined => 4,
-            },
-            Platform => 5,
-        };
-        TileTextureIndex(id)
+impl From<&TileKind> for TileTextureIndex {
+    fn from(value: &TileKind) -> Self {
+        TileTextureIndex(value.id)
     }
 }
 
-impl From<TileTextureIndex> for TileKind {
-    fn from(value: TileTextureIndex) -> Self {
-        use TileKind::*;
-        match value.0 {
-            0 => Square,
-            1 => Slope,
-            2 => Pole(PoleType::Vertical),
-            3 => Pole(PoleType::Horizontal),
-            4 => Pole(PoleType::Combined),
-            5 => Platform,
-            _ => unreachable!(),
-        }
+#[derive(Debug, Serialize, Deserialize, TypeUuid, TypePath)]
+#[uuid = "5b1e0f4c-3c8e-4d0a-9f6b-2e7a1c9d4b30"]
+pub struct TileDefinitions {
+    pub tiles: Vec<TileKind>,
+}
+
+// All known tile kinds, kept in sync with the tile definitions asset
+#[derive(Resource, Default)]
+pub struct TileRegistry {
+    handle: Handle<TileDefinitions>,
+    kinds: Vec<TileKind>,
+}
+
+impl TileRegistry {
+    pub fn get(&self, id: TileTextureIndex) -> Option<&TileKind> {
+        self.kinds.iter().find(|kind| kind.id == id.0)
+    }
+
+    // First tile kind in definition order matching the predicate
+    pub fn find(&self, predicate: impl Fn(&TileKind) -> bool) -> Option<&TileKind> {
+        self.kinds.iter().find(|kind| predicate(kind))
+    }
+
+    pub fn pole(&self, pole: PoleType) -> Option<&TileKind> {
+        self.find(|kind| kind.is_pole() == Some(pole))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TileKind> {
+        self.kinds.iter()
     }
 }
 
-impl From<TileProperties> for Collider {
-    fn from(value: TileProperties) -> Self {
-        let make_right_triangle = |corner, size, dir: Vector| -> Collider {
-            Collider::triangle(
-                corner + Vector::X * size * dir.x,
-                corner + Vector::Y * size * dir.y,
-                corner,
-            )
-        };
-        let dir = match (value.flip.x, value.flip.y) {
-            (false, false) => Vector::new(1., 1.),
-            (true, false) => Vector::new(-1., 1.),
-            (false, true) => Vector::new(1., -1.),
-            (true, true) => Vector::new(-1., -1.),
-        };
+pub fn load_tile_definitions(asset_server: Res<AssetServer>, mut registry: ResMut<TileRegistry>) {
+    registry.handle = asset_server.load(TILE_DEFINITIONS_PATH);
+}
 
-        let cross = Collider::compound(vec![
-            (
-                Position::default(),
-                Rotation::default(),
-                Collider::cuboid(4., 16.),
-            ),
-            (
-                Position::default(),
-                Rotation::default(),
-                Collider::cuboid(16., 4.),
-            ),
-        ]);
-
-        match value.id.0 {
-            0 => Collider::cuboid(16., 16.),
-            1 => make_right_triangle(Vector::new(-8., -8.) * dir, 16., dir),
-            2 => Collider::cuboid(4., 16.),
-            3 => Collider::cuboid(16., 4.),
-            4 => cross,
-            5 => Collider::cuboid(16., 4.),
-            _ => unreachable!(),
+pub fn update_tile_registry(
+    mut registry: ResMut<TileRegistry>,
+    mut asset_events: EventReader<AssetEvent<TileDefinitions>>,
+    definitions: Res<Assets<TileDefinitions>>,
+) {
+    for ev in asset_events.iter() {
+        match ev {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if *handle != registry.handle {
+                    continue;
+                }
+                if let Some(definitions) = definitions.get(handle) {
+                    registry.kinds = definitions.tiles.clone();
+                }
+            }
+            AssetEvent::Removed { .. } => {}
         }
     }
 }
@@ -140,23 +205,55 @@ pub struct InsertTileColliderCommand {
 }
 
 impl Command for InsertTileColliderCommand {
-    fn apply(self, world: &mut bevy::prelude::World) {
+    fn apply(self, world: &mut World) {
         let pos = self.pos + self.kind.offset();
-        let tile_entity = self.tile_entity;
-        world.entity_mut(tile_entity).insert((
+        let mut tile_entity = world.entity_mut(self.tile_entity);
+        // Tile might already have colliders from a previous kind
+        tile_entity.remove::<(
+            RigidBody,
+            Collider,
+            Sensor,
+            Pole,
+            Terrain,
+            Platform,
+            Friction,
+            Hazard,
+            Water,
+        )>();
+
+        let Some(shape) = &self.kind.collider else {
+            return;
+        };
+        tile_entity.insert((
             RigidBody::Static,
-            Collider::from(self.properties),
+            shape.collider(self.properties.flip),
             Position(pos),
         ));
 
-        if let Some(pole) = TileKind::from(self.properties.id).is_pole() {
-            world.entity_mut(tile_entity).insert((Sensor, Pole(pole)));
+        if self.kind.sensor {
+            tile_entity.insert(Sensor);
         } else {
-            world.entity_mut(tile_entity).insert(Terrain);
-        };
+            tile_entity.insert(Terrain);
+        }
+
+        if let Some(pole) = self.kind.is_pole() {
+            tile_entity.insert(Pole(pole));
+        }
+
+        if self.kind.is_platform() {
+            tile_entity.insert(Platform::default());
+        }
+
+        if self.kind.is_hazard() {
+            tile_entity.insert(Hazard);
+        }
+
+        if self.kind.is_water() {
+            tile_entity.insert(Water);
+        }
 
-        if matches!(TileKind::from(self.properties.id), TileKind::Platform) {
-            world.entity_mut(tile_entity).insert(Platform::default());
+        if let Some(friction) = self.kind.friction {
+            tile_entity.insert(Friction::new(friction));
         }
     }
 }
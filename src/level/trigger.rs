@@ -0,0 +1,105 @@
+use bevy::{
+    prelude::*,
+    utils::hashbrown::{HashMap, HashSet},
+};
+use bevy_xpbd_2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::entity::player::{Player, Respawn};
+
+// Hurts the player on contact
+#[derive(Component, Default)]
+pub struct Hazard;
+
+// Tiles with the same name form one zone
+#[derive(Component, Debug, Clone, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct TriggerZone(pub String);
+
+#[derive(Event)]
+pub struct PlayerHurt {
+    pub player: Entity,
+    pub source: Entity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerAction {
+    Enter,
+    Leave,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct TriggerEvent {
+    pub name: String,
+    pub player: Entity,
+    pub action: TriggerAction,
+}
+
+pub fn detect_hazards(
+    players: Query<(Entity, &CollidingEntities), With<Player>>,
+    hazards: Query<(), With<Hazard>>,
+    mut hurt: EventWriter<PlayerHurt>,
+) {
+    for (player, colliding) in players.iter() {
+        if let Some(source) = colliding.0.iter().find(|e| hazards.contains(**e)) {
+            hurt.send(PlayerHurt {
+                player,
+                source: *source,
+            });
+        }
+    }
+}
+
+pub fn respawn_hurt_players(
+    mut hurt: EventReader<PlayerHurt>,
+    mut players: Query<(&Respawn, &mut Position, &mut LinearVelocity), With<Player>>,
+) {
+    for ev in hurt.iter() {
+        let Ok((respawn, mut pos, mut vel)) = players.get_mut(ev.player) else {
+            continue;
+        };
+        pos.0 = respawn.0;
+        vel.0 = Vec2::ZERO;
+    }
+}
+
+pub fn detect_triggers(
+    zones: Query<(&TriggerZone, &CollidingEntities)>,
+    players: Query<(), With<Player>>,
+    mut inside: Local<HashMap<String, HashSet<Entity>>>,
+    mut events: EventWriter<TriggerEvent>,
+) {
+    let mut current: HashMap<String, HashSet<Entity>> = HashMap::new();
+    for (zone, colliding) in zones.iter() {
+        let entry = current.entry(zone.0.clone()).or_default();
+        entry.extend(colliding.0.iter().filter(|e| players.contains(**e)));
+    }
+
+    for (name, now) in current.iter() {
+        let before = inside.get(name);
+        for player in now.iter() {
+            if before.map_or(true, |before| !before.contains(player)) {
+                events.send(TriggerEvent {
+                    name: name.clone(),
+                    player: *player,
+                    action: TriggerAction::Enter,
+                });
+            }
+        }
+    }
+
+    for (name, before) in inside.iter() {
+        let now = current.get(name);
+        for player in before.iter() {
+            if now.map_or(true, |now| !now.contains(player)) {
+                events.send(TriggerEvent {
+                    name: name.clone(),
+                    player: *player,
+                    action: TriggerAction::Leave,
+                });
+            }
+        }
+    }
+
+    *inside = current;
+}
@@ -8,6 +8,7 @@ pub mod level;
 pub mod lifetime;
 pub mod nono;
 pub mod phys;
+pub mod replay;
 pub mod sokoban;
 pub mod ui;
 pub mod util;
@@ -6,7 +6,8 @@ pub struct LifetimePlugin;
 
 impl Plugin for LifetimePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(PostUpdate, handle_lifetimes);
+        // Despawning bodies changes the simulation, so lifetimes count fixed ticks
+        app.add_systems(FixedUpdate, handle_lifetimes);
     }
 }
 
@@ -21,16 +22,21 @@ impl Lifetime {
             lifetime: Timer::new(Duration::from_secs_f32(lifetime), TimerMode::Once),
         }
     }
+
+    // Whether the next tick by `delta` despawns the entity
+    pub fn will_expire(&self, delta: Duration) -> bool {
+        self.lifetime.elapsed() + delta >= self.lifetime.duration()
+    }
 }
 
 pub fn handle_lifetimes(
     mut cmds: Commands,
     mut lifetimes: Query<(Entity, &mut Lifetime)>,
-    time: Res<Time>,
+    time: Res<FixedTime>,
 ) {
     // TODO
     for (entity, mut lifetime) in lifetimes.iter_mut() {
-        lifetime.lifetime.tick(time.delta());
+        lifetime.lifetime.tick(time.period);
         if lifetime.lifetime.finished() {
             cmds.entity(entity).despawn_recursive();
         }
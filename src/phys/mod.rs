@@ -1,26 +1,82 @@
 use bevy::prelude::*;
 use bevy_xpbd_2d::{prelude::*, PostProcessCollisions};
 
-use crate::entity::holdable::{pick_up, throw};
+use crate::{
+    entity::{
+        creature::{creature_ai, CreatureAi},
+        holdable::{carry_held, explode_bombs, pick_up, throw, Holdable},
+        projectile::{
+            apply_projectile_hits, break_links, detect_projectile_hits, track_thrown, ProjectileHit,
+        },
+    },
+    lifetime::handle_lifetimes,
+};
 
 use self::{
-    movement::{MovementPlugin, PoleClimb},
-    terrain::{handle_platforms, Pole},
+    movement::{consume_movement_input, latch_virtual_input, MovementPlugin, MovementSet},
+    terrain::{handle_platforms, tick_drop_through, Pole},
+    water::{apply_buoyancy, update_submerged, Density, Submerged},
 };
 
 pub mod movement;
 pub mod spatial;
 pub mod terrain;
 pub mod verlet;
+pub mod water;
+
+// Both gameplay and physics advance in steps of this size
+pub const TIMESTEP: f32 = 1. / 60.;
 
 pub struct PhysPlugin;
 
 impl Plugin for PhysPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((MovementPlugin, PhysicsPlugins::default()));
+        app.add_plugins((MovementPlugin, PhysicsPlugins::new(FixedUpdate)));
+        app.insert_resource(FixedTime::new_from_secs(TIMESTEP))
+            .insert_resource(PhysicsTimestep::FixedOnce(TIMESTEP));
         app.add_systems(PostProcessCollisions, handle_platforms);
-        app.add_systems(Update, (pick_up, throw));
+        // Holding reads the tick's final inputs, after replays and AI wrote them and before they
+        // are consumed, throws overwrite carrying
+        app.add_systems(
+            FixedUpdate,
+            (
+                (pick_up, throw.after(carry_held))
+                    .after(MovementSet)
+                    .before(consume_movement_input),
+                explode_bombs
+                    .before(handle_lifetimes)
+                    .before(PhysicsSet::Prepare),
+            ),
+        );
+        app.add_systems(
+            FixedUpdate,
+            (tick_drop_through, carry_held, track_thrown)
+                .after(MovementSet)
+                .before(PhysicsSet::Prepare),
+        );
+        app.add_systems(
+            FixedUpdate,
+            (
+                (detect_projectile_hits, apply_projectile_hits).chain(),
+                break_links,
+            )
+                .after(PhysicsSet::Sync),
+        );
+        app.add_event::<ProjectileHit>();
+        app.add_systems(FixedUpdate, creature_ai.before(latch_virtual_input));
+        app.add_systems(
+            FixedUpdate,
+            (
+                update_submerged.before(MovementSet),
+                apply_buoyancy
+                    .after(update_submerged)
+                    .before(PhysicsSet::Prepare),
+            ),
+        );
         app.register_type::<Pole>();
-        app.register_type::<PoleClimb>();
+        app.register_type::<Holdable>();
+        app.register_type::<Density>();
+        app.register_type::<Submerged>();
+        app.register_type::<CreatureAi>();
     }
 }
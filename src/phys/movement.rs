@@ -1,38 +1,86 @@
 use std::time::Duration;
 
-use bevy::{prelude::*, time::Stopwatch, utils::hashbrown::HashMap};
+use bevy::{prelude::*, reflect::TypeUuid, time::Stopwatch, utils::hashbrown::HashMap};
+use bevy_common_assets::ron::RonAssetPlugin;
 use bevy_xpbd_2d::{
     math::{Scalar, Vector},
     prelude::*,
 };
-use leafwing_input_manager::prelude::*;
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::*};
+use serde::{Deserialize, Serialize};
 
-use crate::entity::player::Player;
-
-use super::terrain::{Pole, PoleType, Terrain};
+use super::{
+    terrain::{DropThrough, Platform, Pole, PoleType, Terrain},
+    water::Submerged,
+};
 
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(InputManagerPlugin::<ActionKind>::default());
+        app.add_plugins((
+            InputManagerPlugin::<ActionKind>::default(),
+            RonAssetPlugin::<MovementConfig>::new(&["movement.ron"]),
+        ));
+        app.add_systems(Startup, load_movement_config);
+        app.add_systems(Update, update_movement_config);
+        app.add_systems(
+            PreUpdate,
+            latch_movement_input.after(InputManagerSystem::Update),
+        );
+        // Runs in lockstep with physics so movement doesn't depend on the frame rate
         app.add_systems(
-            Update,
+            FixedUpdate,
             (
-                setup_movement_state,
-                (horizontal_movement, jump, wall_jump).after(setup_movement_state),
-                pole_climb,
-                pole_movement,
-                pole_gravity,
-            ),
+                (
+                    buffer_inputs,
+                    setup_movement_state,
+                    update_movement_mode,
+                    apply_mode_transitions,
+                )
+                    .chain(),
+                (
+                    horizontal_movement,
+                    slope_movement,
+                    // Mode changes from highest to lowest priority, a system leaves entities
+                    // alone once an earlier one requested a transition for them this tick
+                    ledge_climb,
+                    pole_movement,
+                    pole_climb,
+                    swim,
+                    drop_through,
+                    jump,
+                    wall_jump,
+                    ledge_grab,
+                    crouch,
+                )
+                    .chain()
+                    .after(apply_mode_transitions),
+            )
+                .in_set(MovementSet)
+                .before(PhysicsSet::Prepare),
+        );
+        app.add_systems(FixedUpdate, latch_virtual_input.before(MovementSet));
+        app.add_systems(
+            FixedUpdate,
+            consume_movement_input
+                .after(MovementSet)
+                .before(PhysicsSet::Prepare),
         );
-        app.insert_resource(DisabledInputs::default())
-            .insert_resource(MovementState::default());
+        app.add_event::<MovementModeChanged>();
+        app.init_resource::<MovementConfig>()
+            .init_resource::<MovementConfigHandle>();
         app.register_type::<MovementState>();
+        app.register_type::<MovementConfig>();
+        app.register_type::<MovementInput>();
+        app.register_type::<VirtualController>();
+        app.register_type::<PlayerState>();
+        app.register_type::<MovementMode>();
+        app.register_type::<CrouchShape>();
     }
 }
 
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
 pub enum LookDir {
     Left,
     Right,
@@ -63,7 +111,10 @@ impl LookDir {
     }
 }
 
-#[derive(Component)]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MovementSet;
+
+#[derive(Component, Default)]
 pub struct Controllable;
 
 #[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect)]
@@ -73,6 +124,8 @@ pub enum ActionKind {
     Left,
     Right,
     Jump,
+    PickUp,
+    Throw,
 }
 
 #[derive(Event)]
@@ -81,10 +134,171 @@ pub enum MovementAction {
     Jump,
 }
 
+// Actions the movement systems read, presses are kept until a fixed tick consumes them
+#[derive(
+    Component, Default, Clone, Copy, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize,
+)]
+#[reflect(Component)]
+pub struct MovementInput {
+    pressed: u8,
+    just_pressed: u8,
+    just_released: u8,
+}
+
+impl MovementInput {
+    fn bit(action: ActionKind) -> u8 {
+        1 << action.index()
+    }
+
+    pub fn pressed(&self, action: ActionKind) -> bool {
+        self.pressed & Self::bit(action) != 0
+    }
+
+    pub fn just_pressed(&self, action: ActionKind) -> bool {
+        self.just_pressed & Self::bit(action) != 0
+    }
+
+    pub fn just_released(&self, action: ActionKind) -> bool {
+        self.just_released & Self::bit(action) != 0
+    }
+
+    pub fn set(&mut self, action: ActionKind, pressed: bool) {
+        let bit = Self::bit(action);
+        let was_pressed = self.pressed(action);
+        if pressed && !was_pressed {
+            self.just_pressed |= bit;
+        }
+        if !pressed && was_pressed {
+            self.just_released |= bit;
+        }
+        if pressed {
+            self.pressed |= bit;
+        } else {
+            self.pressed &= !bit;
+        }
+    }
+
+    pub fn consume(&mut self) {
+        self.just_pressed = 0;
+        self.just_released = 0;
+    }
+}
+
+// Remembers recent presses so they can still be acted on shortly after
+#[derive(Component, Default)]
+pub struct InputBuffer(HashMap<ActionKind, Stopwatch>);
+
+impl InputBuffer {
+    pub fn press(&mut self, action: ActionKind) {
+        self.0.insert(action, Stopwatch::new());
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        for stopwatch in self.0.values_mut() {
+            stopwatch.tick(delta);
+        }
+    }
+
+    // Was the action pressed within the last `window` seconds and not consumed yet
+    pub fn buffered(&self, action: ActionKind, window: f32) -> bool {
+        self.0
+            .get(&action)
+            .map_or(false, |stopwatch| stopwatch.elapsed_secs() <= window)
+    }
+
+    pub fn consume(&mut self, action: ActionKind) {
+        self.0.remove(&action);
+    }
+}
+
+fn buffer_inputs(
+    mut buffers: Query<(&mut InputBuffer, &MovementInput)>,
+    time: Res<FixedTime>,
+    config: Res<MovementConfig>,
+) {
+    let max_window = config.jump_buffer_time.max(config.climb_buffer_time);
+    for (mut buffer, input) in buffers.iter_mut() {
+        buffer.tick(time.period);
+        buffer
+            .0
+            .retain(|_, stopwatch| stopwatch.elapsed_secs() <= max_window);
+
+        for action in [ActionKind::Jump, ActionKind::Up] {
+            if input.just_pressed(action) {
+                buffer.press(action);
+            }
+        }
+    }
+}
+
+fn latch_movement_input(mut inputs: Query<(&mut MovementInput, &ActionState<ActionKind>)>) {
+    for (mut input, action_state) in inputs.iter_mut() {
+        for action in ActionKind::variants() {
+            input.set(action, action_state.pressed(action));
+        }
+    }
+}
+
+// Buttons held on a controller that isn't backed by a real input device
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct VirtualController {
+    pressed: u8,
+}
+
+impl VirtualController {
+    pub fn pressed(&self, action: ActionKind) -> bool {
+        self.pressed & MovementInput::bit(action) != 0
+    }
+
+    pub fn set(&mut self, action: ActionKind, pressed: bool) {
+        let bit = MovementInput::bit(action);
+        if pressed {
+            self.pressed |= bit;
+        } else {
+            self.pressed &= !bit;
+        }
+    }
+
+    pub fn press(&mut self, action: ActionKind) {
+        self.set(action, true);
+    }
+
+    pub fn release_all(&mut self) {
+        self.pressed = 0;
+    }
+}
+
+// Virtual controllers are usually written from fixed systems, so they are latched every tick
+pub fn latch_virtual_input(mut inputs: Query<(&mut MovementInput, &VirtualController)>) {
+    for (mut input, controller) in inputs.iter_mut() {
+        for action in ActionKind::variants() {
+            input.set(action, controller.pressed(action));
+        }
+    }
+}
+
+pub fn consume_movement_input(mut inputs: Query<&mut MovementInput>) {
+    for mut input in inputs.iter_mut() {
+        input.consume();
+    }
+}
+
+// Everything the movement systems need, independent of where the inputs come from
+#[derive(Bundle, Default)]
+pub struct MovementBundle {
+    controllable: Controllable,
+    movement_input: MovementInput,
+    input_buffer: InputBuffer,
+    state: PlayerState,
+    movement_state: MovementState,
+}
+
+// Movement driven by the keyboard
 #[derive(Bundle)]
 pub struct Control {
-    controllable: Controllable,
     input: InputManagerBundle<ActionKind>,
+    movement: MovementBundle,
 }
 
 impl Default for Control {
@@ -97,42 +311,146 @@ impl Default for Control {
         input_map.insert(KeyCode::A, Left);
         input_map.insert(KeyCode::D, Right);
         input_map.insert(KeyCode::Space, Jump);
+        input_map.insert(KeyCode::H, PickUp);
+        input_map.insert(KeyCode::X, Throw);
         Self {
-            controllable: Controllable,
             input: InputManagerBundle {
                 input_map,
                 ..default()
             },
+            movement: MovementBundle::default(),
         }
     }
 }
 
-#[derive(Component)]
-pub struct MovementProperties {
+// Movement driven by a `VirtualController`, e.g. from an AI
+#[derive(Bundle, Default)]
+pub struct VirtualControl {
+    controller: VirtualController,
+    movement: MovementBundle,
+}
+
+pub const MOVEMENT_CONFIG_PATH: &str = "config/player.movement.ron";
+
+// Tuning values used by all movement systems, speeds are in pixels per second
+// and accelerations in pixels per second squared
+#[derive(Resource, Debug, Clone, Reflect, Serialize, Deserialize, TypeUuid)]
+#[reflect(Resource)]
+#[uuid = "0c9c8a52-6a3e-4a53-8f43-5d3f0f0e7b21"]
+#[serde(default)]
+pub struct MovementConfig {
+    pub max_speed: Scalar,
+    pub crouch_speed: Scalar,
     pub acceleration: Scalar,
-    pub damping: Scalar,
     pub jump_impulse: Scalar,
+    // Acceleration while jump is held shortly after jumping
+    pub jump_extension: Scalar,
+    pub jump_extension_time: f32,
+    pub coyote_time: f32,
+    // How long a jump press is remembered when it can't be acted on yet
+    pub jump_buffer_time: f32,
+    // Same for pressing up to grab a pole
+    pub climb_buffer_time: f32,
+    pub wall_jump_height: Scalar,
+    pub wall_jump_push: Scalar,
+    // How long moving back towards the wall is disabled after a wall jump
+    pub wall_jump_lock: f32,
+    pub wall_slide_speed: Scalar,
+    pub pole_speed: Scalar,
+    pub pole_jump_height: Scalar,
+    pub pole_jump_push: Scalar,
+    // Poles can't be grabbed again right after jumping off of one
+    pub pole_regrab_time: f32,
+    pub swim_speed: Scalar,
+    pub swim_acceleration: Scalar,
+    // How quickly the player stops when not swimming in any direction, per second
+    pub swim_drag: Scalar,
+    pub swim_dash_speed: Scalar,
+    pub swim_dash_time: f32,
+    // Time after a dash before the next one
+    pub swim_dash_cooldown: f32,
+    // Fraction of the body under water to start swimming, stops at half of it
+    pub swim_depth: Scalar,
+    // How far above the player's head there has to be free space to grab a ledge
+    pub ledge_grab_height: Scalar,
+    // Ledges can't be grabbed again right after letting go of one
+    pub ledge_regrab_time: f32,
+    // How long getting hit takes away control
+    pub stun_time: f32,
+    // Steepest ground the player can still stand on, in radians
+    pub max_walkable_angle: Scalar,
+    // How far the player gets pulled down to stay on the ground when walking down slopes
+    pub ground_snap_distance: Scalar,
 }
 
-impl MovementProperties {
-    pub fn new(acceleration: Scalar, damping: Scalar, jump_impulse: Scalar) -> Self {
+impl Default for MovementConfig {
+    fn default() -> Self {
         Self {
-            acceleration,
-            damping,
-            jump_impulse,
+            max_speed: 128.,
+            crouch_speed: 48.,
+            acceleration: 360.,
+            jump_impulse: 96.,
+            jump_extension: 240.,
+            jump_extension_time: 0.2,
+            coyote_time: 0.15,
+            jump_buffer_time: 0.1,
+            climb_buffer_time: 0.1,
+            wall_jump_height: 128.,
+            wall_jump_push: 192.,
+            wall_jump_lock: 0.5,
+            wall_slide_speed: 30.,
+            pole_speed: 64.,
+            pole_jump_height: 96.,
+            pole_jump_push: 96.,
+            pole_regrab_time: 0.2,
+            swim_speed: 80.,
+            swim_acceleration: 480.,
+            swim_drag: 4.,
+            swim_dash_speed: 192.,
+            swim_dash_time: 0.2,
+            swim_dash_cooldown: 0.4,
+            swim_depth: 0.6,
+            ledge_grab_height: 4.,
+            ledge_regrab_time: 0.3,
+            stun_time: 0.6,
+            max_walkable_angle: 0.9,
+            ground_snap_distance: 4.,
         }
     }
 }
 
-impl Default for MovementProperties {
-    fn default() -> Self {
-        Self::new(30., 0.9, 7.)
+#[derive(Resource, Default)]
+pub struct MovementConfigHandle(pub Handle<MovementConfig>);
+
+fn load_movement_config(asset_server: Res<AssetServer>, mut handle: ResMut<MovementConfigHandle>) {
+    handle.0 = asset_server.load(MOVEMENT_CONFIG_PATH);
+}
+
+// Copies the loaded config into the resource, also picks up changes when the file is hot reloaded
+fn update_movement_config(
+    mut config: ResMut<MovementConfig>,
+    handle: Res<MovementConfigHandle>,
+    configs: Res<Assets<MovementConfig>>,
+    mut asset_events: EventReader<AssetEvent<MovementConfig>>,
+) {
+    for ev in asset_events.iter() {
+        match ev {
+            AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed } => {
+                if *changed != handle.0 {
+                    continue;
+                }
+                if let Some(loaded) = configs.get(changed) {
+                    *config = loaded.clone();
+                }
+            }
+            AssetEvent::Removed { .. } => {}
+        }
     }
 }
 
 fn keyboard_input(
     mut movement_events: EventWriter<MovementAction>,
-    action_state_query: Query<&ActionState<ActionKind>>,
+    action_state_query: Query<&MovementInput>,
 ) {
     let Ok(action_state) = action_state_query.get_single() else {
         return;
@@ -150,321 +468,1139 @@ fn keyboard_input(
     }
 }
 
-#[derive(Resource, Default, Deref, DerefMut)]
-pub struct DisabledInputs(HashMap<ActionKind, Timer>);
+// What the player is currently doing, decides which movement systems apply
+#[derive(Default, Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum MovementMode {
+    Grounded,
+    // On the ground with a shorter body
+    Crouching,
+    #[default]
+    Airborne,
+    // Rising from a jump off the ground
+    Jumping,
+    // Falling along a wall on the given side
+    WallSlide(LookDir),
+    // Pushed off a wall on the given side, moving back towards it is locked for a moment
+    WallJump(LookDir),
+    PoleClimb(PoleType),
+    // Hanging from a ledge on the given side
+    LedgeHang(LookDir),
+    // Moving freely in all directions under water
+    Swimming,
+    // Short burst of speed while swimming
+    SwimDash,
+    // Being carried by another entity
+    Held,
+    // Knocked out of control for a moment, e.g. after getting hit
+    Stunned,
+}
 
-#[derive(Resource, Default, Reflect)]
-#[reflect(Resource)]
+impl MovementMode {
+    // Modes in which walking, jumping and gravity work as usual
+    pub fn is_free(&self) -> bool {
+        self.has_gravity() && *self != MovementMode::Stunned
+    }
+
+    pub fn has_gravity(&self) -> bool {
+        !matches!(
+            self,
+            MovementMode::PoleClimb(_)
+                | MovementMode::LedgeHang(_)
+                | MovementMode::Swimming
+                | MovementMode::SwimDash
+                | MovementMode::Held
+        )
+    }
+}
+
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct PlayerState {
+    mode: MovementMode,
+    previous: MovementMode,
+    // Requested transition, applied at the start of the next tick
+    next: Option<MovementMode>,
+    #[reflect(ignore)]
+    elapsed: Stopwatch,
+}
+
+impl PlayerState {
+    pub fn mode(&self) -> MovementMode {
+        self.mode
+    }
+
+    pub fn previous(&self) -> MovementMode {
+        self.previous
+    }
+
+    pub fn time_in_mode(&self) -> f32 {
+        self.elapsed.elapsed_secs()
+    }
+
+    pub fn is(&self, mode: MovementMode) -> bool {
+        self.mode == mode
+    }
+
+    pub fn set(&mut self, mode: MovementMode) {
+        self.next = Some(mode);
+    }
+
+    // A transition was already requested this tick
+    pub fn is_changing(&self) -> bool {
+        self.next.is_some()
+    }
+}
+
+#[derive(Event)]
+pub struct MovementModeChanged {
+    pub entity: Entity,
+    pub from: MovementMode,
+    pub to: MovementMode,
+}
+
+// Body sizes to switch between when crouching
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct CrouchShape {
+    pub standing: Vector,
+    pub crouching: Vector,
+}
+
+impl CrouchShape {
+    // Crouching halves the height
+    pub fn new(standing: Vector) -> Self {
+        Self {
+            standing,
+            crouching: Vector::new(standing.x, standing.y / 2.),
+        }
+    }
+
+    // Overhead has to be free of terrain to stand up, with the feet staying in place
+    fn can_stand(
+        &self,
+        entity: Entity,
+        pos: Vector,
+        spatial_query: &SpatialQuery,
+        q_terrain: &Query<(), With<Terrain>>,
+    ) -> bool {
+        let center = pos + Vector::Y * (self.standing.y - self.crouching.y) / 2.;
+        !spatial_query
+            .shape_intersections(
+                &Collider::cuboid(self.standing.x - 0.5, self.standing.y - 0.5),
+                center,
+                0.,
+                SpatialQueryFilter::new().without_entities([entity]),
+            )
+            .into_iter()
+            .any(|entity| q_terrain.contains(entity))
+    }
+}
+
+fn resize_body(
+    size: Vector,
+    old_size: Vector,
+    pos: &mut Position,
+    collider: &mut Collider,
+    caster: &mut ShapeCaster,
+) {
+    *collider = Collider::cuboid(size.x, size.y);
+    caster.shape = Collider::cuboid(size.x - 0.5, size.y - 0.5);
+    // Keep the feet in place
+    pos.y += (size.y - old_size.y) / 2.;
+}
+
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
 pub struct MovementState {
     pub grounded: bool,
+    pub ground_normal: Vector,
+    pub on_platform: bool,
     // TODO combine these into one
     pub facing_wall: bool,
     pub wall_left: bool,
     pub wall_right: bool,
+    pub ledge: Option<Ledge>,
     pub falling: bool,
 }
 
-// TODO Think about extract movement constants. Jump height, horizontal velocity, wall jump impulse, etc.
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct Ledge {
+    pub side: LookDir,
+    // Top corner of the wall
+    pub corner: Vector,
+}
+
+impl Default for MovementState {
+    fn default() -> Self {
+        Self {
+            grounded: false,
+            ground_normal: Vector::Y,
+            on_platform: false,
+            facing_wall: false,
+            wall_left: false,
+            wall_right: false,
+            ledge: None,
+            falling: false,
+        }
+    }
+}
+
 fn setup_movement_state(
-    player_query: Query<
+    mut player_query: Query<
         (
             Entity,
-            &Position,
+            &mut MovementState,
+            &mut Position,
+            &Rotation,
             &LinearVelocity,
             &ShapeHits,
+            &ShapeCaster,
             &LookDir,
             &ColliderAabb,
         ),
         With<Controllable>,
     >,
     q_terrain: Query<Entity, With<Terrain>>,
+    q_platforms: Query<Entity, With<Platform>>,
     spatial_query: SpatialQuery,
-    mut movement_state: ResMut<MovementState>,
+    config: Res<MovementConfig>,
 ) {
-    let Ok((player_entity, pos, vel, ground, look_dir, collider_aabb)) = player_query.get_single()
-    else {
-        return;
-    };
+    let walkable =
+        |normal: Vector| normal.angle_between(Vector::Y).abs() <= config.max_walkable_angle;
 
-    let grounded = !ground.is_empty();
-    let falling = vel.y < 0.;
+    for (
+        player_entity,
+        mut movement_state,
+        mut pos,
+        rotation,
+        vel,
+        ground,
+        caster,
+        look_dir,
+        collider_aabb,
+    ) in player_query.iter_mut()
+    {
+        // Only count surfaces that aren't too steep as ground
+        let ground_hit = ground
+            .iter()
+            .map(|hit| (hit.entity, rotation.rotate(-hit.normal2)))
+            .find(|(_, normal)| walkable(*normal));
+        let mut grounded = ground_hit.is_some();
+        let mut ground_normal = ground_hit.map_or(Vector::Y, |(_, normal)| normal);
+        let on_platform = ground_hit.map_or(false, |(entity, _)| q_platforms.contains(entity));
+        let falling = vel.y < 0.;
 
-    // Casts a ray just outside the player into the given look direction
-    let ray_in_look_dir = |dir: LookDir| -> bool {
-        spatial_query
-            .cast_ray(
+        // Walking down a slope can lift the player off the ground for a moment, pull them back down
+        if !grounded && movement_state.grounded && vel.y <= 0. {
+            let snap = spatial_query.cast_shape(
+                &caster.shape,
                 **pos,
-                dir.as_vec(),
-                collider_aabb.half_extents().x + 0.5,
+                0.,
+                Vector::NEG_Y,
+                config.ground_snap_distance,
                 true,
                 SpatialQueryFilter::new().without_entities([player_entity]),
-            )
-            .map_or(false, |hit| q_terrain.get(hit.entity).ok().is_some())
-    };
+            );
+            if let Some(hit) = snap {
+                let normal = rotation.rotate(-hit.normal2);
+                if walkable(normal) {
+                    pos.y -= hit.time_of_impact;
+                    grounded = true;
+                    ground_normal = normal;
+                }
+            }
+        }
+
+        // Casts a ray just outside the player into the given look direction
+        let ray_in_look_dir = |dir: LookDir| -> bool {
+            spatial_query
+                .cast_ray(
+                    **pos,
+                    dir.as_vec(),
+                    collider_aabb.half_extents().x + 0.5,
+                    true,
+                    SpatialQueryFilter::new().without_entities([player_entity]),
+                )
+                .map_or(false, |hit| q_terrain.get(hit.entity).ok().is_some())
+        };
+
+        let facing_wall = ray_in_look_dir(*look_dir);
+        let wall_left = ray_in_look_dir(LookDir::Left);
+        let wall_right = ray_in_look_dir(LookDir::Right);
 
-    let facing_wall = ray_in_look_dir(look_dir.clone());
-    let wall_left = ray_in_look_dir(LookDir::Left);
-    let wall_right = ray_in_look_dir(LookDir::Right);
+        // Wall at head height with free space right above it
+        let half_extents = collider_aabb.half_extents();
+        let cast_terrain = |origin: Vector, dir: Vector, max_toi: Scalar| {
+            spatial_query
+                .cast_ray(
+                    origin,
+                    dir,
+                    max_toi,
+                    true,
+                    SpatialQueryFilter::new().without_entities([player_entity]),
+                )
+                .filter(|hit| q_terrain.contains(hit.entity))
+        };
+        let dir = look_dir.as_vec();
+        let head = **pos + Vector::Y * (half_extents.y - 1.);
+        let above = **pos + Vector::Y * (half_extents.y + config.ledge_grab_height);
+        let reach = half_extents.x + 0.5;
+        let ledge = match (
+            cast_terrain(head, dir, reach),
+            cast_terrain(above, dir, reach),
+        ) {
+            (Some(wall), None) => {
+                let wall_x = head.x + dir.x * wall.time_of_impact;
+                // Find the top of the wall just behind its face
+                cast_terrain(
+                    Vector::new(wall_x + dir.x, above.y),
+                    Vector::NEG_Y,
+                    config.ledge_grab_height + 1.,
+                )
+                .map(|top| Ledge {
+                    side: *look_dir,
+                    corner: Vector::new(wall_x, above.y - top.time_of_impact),
+                })
+            }
+            _ => None,
+        };
 
-    *movement_state = MovementState {
-        grounded,
-        facing_wall,
-        wall_left,
-        wall_right,
-        falling,
+        *movement_state = MovementState {
+            grounded,
+            ground_normal,
+            on_platform,
+            facing_wall,
+            wall_left,
+            wall_right,
+            ledge,
+            falling,
+        }
     }
 }
 
 fn horizontal_movement(
-    action_state_query: Query<&ActionState<ActionKind>>,
     mut player_query: Query<
-        (&mut LinearVelocity, &mut LookDir),
-        (With<Controllable>, Without<PoleClimb>),
+        (
+            &MovementInput,
+            &PlayerState,
+            &mut LinearVelocity,
+            &mut LookDir,
+        ),
+        With<Controllable>,
     >,
-    movement_state: Res<MovementState>,
-    disabled_inputs: Res<DisabledInputs>,
+    config: Res<MovementConfig>,
+    time: Res<FixedTime>,
 ) {
-    let Ok(action_state) = action_state_query.get_single() else {
-        return;
-    };
+    let dt = time.period.as_secs_f32();
 
-    let Ok((mut vel, mut look_dir)) = player_query.get_single_mut() else {
-        return;
-    };
+    for (action_state, state, mut vel, mut look_dir) in player_query.iter_mut() {
+        if !state.mode().is_free() {
+            continue;
+        }
 
-    let MovementState {
-        falling,
-        wall_left: left_wall,
-        wall_right: right_wall,
-        ..
-    } = *movement_state;
+        // Moving back towards the wall is disabled right after a wall jump
+        let locked = match state.mode() {
+            MovementMode::WallJump(wall) => Some(wall),
+            _ => None,
+        };
 
-    let max_speed = 128.;
+        if action_state.pressed(ActionKind::Left) && locked != Some(LookDir::Left) {
+            vel.x -= config.acceleration * dt;
+            *look_dir = LookDir::Left;
+        }
+        if action_state.pressed(ActionKind::Right) && locked != Some(LookDir::Right) {
+            vel.x += config.acceleration * dt;
+            *look_dir = LookDir::Right;
+        }
 
-    // Disabled movement from wall jump
-    let left_enabled = disabled_inputs
-        .get(&ActionKind::Left)
-        .map_or(true, |timer| timer.finished());
-    let right_enabled = disabled_inputs
-        .get(&ActionKind::Right)
-        .map_or(true, |timer| timer.finished());
+        if let MovementMode::WallSlide(_) = state.mode() {
+            vel.y = -config.wall_slide_speed;
+        }
 
-    if action_state.pressed(ActionKind::Left) && left_enabled {
-        vel.x -= 6.;
-        *look_dir = LookDir::Left;
+        // Never exceed max speed
+        let max_speed = if state.is(MovementMode::Crouching) {
+            config.crouch_speed
+        } else {
+            config.max_speed
+        };
+        vel.x = vel.x.clamp(-max_speed, max_speed);
+    }
+}
 
-        // Slide down walls
-        if left_wall && falling {
-            vel.y = -30.;
+// Keeps the player on sloped ground by moving along it instead of into or away from it
+fn slope_movement(
+    mut player_query: Query<
+        (&mut LinearVelocity, &PlayerState, &MovementState),
+        With<Controllable>,
+    >,
+) {
+    for (mut vel, state, movement_state) in player_query.iter_mut() {
+        if !state.mode().is_free() {
+            continue;
         }
-    }
-    if action_state.pressed(ActionKind::Right) && right_enabled {
-        vel.x += 6.;
-        *look_dir = LookDir::Right;
 
-        // Slide down walls
-        if right_wall && falling {
-            vel.y = -30.;
+        let MovementState {
+            grounded,
+            ground_normal,
+            ..
+        } = *movement_state;
+
+        // Flat ground needs no correction
+        if !grounded || ground_normal.y >= 1. - Scalar::EPSILON {
+            continue;
         }
-    }
 
-    // Never exceed max speed
-    vel.x = vel.x.clamp(-max_speed, max_speed);
+        // Don't glue the player to the ground when moving away from it, e.g. when jumping
+        if vel.dot(ground_normal) > 1. {
+            continue;
+        }
+
+        // Keep horizontal speed the same both uphill and downhill
+        let tangent = Vector::new(ground_normal.y, -ground_normal.x);
+        vel.y = vel.x * tangent.y / tangent.x;
+    }
 }
 
-fn jump(
-    action_state_query: Query<&ActionState<ActionKind>>,
-    mut player_query: Query<&mut LinearVelocity, (With<Controllable>, Without<PoleClimb>)>,
-    time: Res<Time>,
-    movement_state: Res<MovementState>,
-    mut jump_extender: Local<Stopwatch>,
-    mut coyote: Local<Stopwatch>,
+// Derives the mode from the surroundings, unless a system requested a transition this tick
+fn update_movement_mode(
+    mut player_query: Query<
+        (
+            &MovementInput,
+            &MovementState,
+            Option<&Submerged>,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
+    >,
+    config: Res<MovementConfig>,
+    time: Res<FixedTime>,
 ) {
-    let Ok(action_state) = action_state_query.get_single() else {
-        return;
-    };
+    for (action_state, movement_state, submerged, mut state) in player_query.iter_mut() {
+        state.elapsed.tick(time.period);
+        if state.is_changing() {
+            continue;
+        }
 
-    let Ok(mut vel) = player_query.get_single_mut() else {
-        return;
-    };
+        let depth = submerged.map_or(0., |submerged| submerged.0);
+        let swimming = matches!(
+            state.mode(),
+            MovementMode::Swimming | MovementMode::SwimDash
+        );
+        if state.mode().is_free() && depth >= config.swim_depth {
+            state.set(MovementMode::Swimming);
+            continue;
+        }
+        // Leave the water with some margin so bobbing at the surface doesn't toggle swimming
+        if swimming && depth < config.swim_depth / 2. {
+            state.set(if movement_state.grounded {
+                MovementMode::Grounded
+            } else {
+                MovementMode::Airborne
+            });
+            continue;
+        }
+        if state.is(MovementMode::SwimDash) && state.time_in_mode() >= config.swim_dash_time {
+            state.set(MovementMode::Swimming);
+            continue;
+        }
 
-    jump_extender.tick(time.delta());
-    coyote.tick(time.delta());
+        let MovementState {
+            grounded,
+            falling,
+            wall_left,
+            wall_right,
+            ..
+        } = *movement_state;
 
-    let MovementState {
-        grounded, falling, ..
-    } = *movement_state;
+        let next = match state.mode() {
+            // Only left through explicit transitions
+            MovementMode::PoleClimb(_)
+            | MovementMode::LedgeHang(_)
+            | MovementMode::Swimming
+            | MovementMode::SwimDash
+            | MovementMode::Held
+            | MovementMode::Crouching => continue,
+            MovementMode::Stunned if state.time_in_mode() < config.stun_time => continue,
+            // Still rising, the ground check can hit for a moment right after jumping
+            MovementMode::Jumping
+                if !falling && (!grounded || state.time_in_mode() < config.jump_extension_time) =>
+            {
+                continue
+            }
+            MovementMode::WallJump(_)
+                if !grounded && state.time_in_mode() < config.wall_jump_lock =>
+            {
+                continue
+            }
+            _ if grounded => MovementMode::Grounded,
+            _ if falling && wall_left && action_state.pressed(ActionKind::Left) => {
+                MovementMode::WallSlide(LookDir::Left)
+            }
+            _ if falling && wall_right && action_state.pressed(ActionKind::Right) => {
+                MovementMode::WallSlide(LookDir::Right)
+            }
+            _ => MovementMode::Airborne,
+        };
 
-    if grounded {
-        coyote.reset();
+        if next != state.mode() {
+            state.set(next);
+        }
     }
+}
 
-    let can_coyote = coyote.elapsed_secs() < 0.15 && falling;
-    let can_jump = grounded || can_coyote;
+fn apply_mode_transitions(
+    mut player_query: Query<(
+        Entity,
+        &mut PlayerState,
+        Option<&mut GravityScale>,
+        Option<(&CrouchShape, &mut Position, &mut Collider, &mut ShapeCaster)>,
+    )>,
+    mut mode_changed: EventWriter<MovementModeChanged>,
+) {
+    for (entity, mut state, mut gravity, mut body) in player_query.iter_mut() {
+        let Some(next) = state.next.take() else {
+            continue;
+        };
+        let from = state.mode();
+        if next == from {
+            continue;
+        }
+
+        // Exit hooks
+        if !from.has_gravity() {
+            if let Some(gravity) = gravity.as_mut() {
+                gravity.0 = 1.;
+            }
+        }
+        if from == MovementMode::Crouching {
+            if let Some((shape, pos, collider, caster)) = body.as_mut() {
+                resize_body(shape.standing, shape.crouching, pos, collider, caster);
+            }
+        }
 
-    if action_state.just_pressed(ActionKind::Jump) && can_jump {
-        vel.y = 96.;
-        jump_extender.reset();
+        // Enter hooks
+        if !next.has_gravity() {
+            if let Some(gravity) = gravity.as_mut() {
+                gravity.0 = 0.;
+            }
+        }
+        if next == MovementMode::Crouching {
+            if let Some((shape, pos, collider, caster)) = body.as_mut() {
+                resize_body(shape.crouching, shape.standing, pos, collider, caster);
+            }
+        }
+
+        state.previous = from;
+        state.mode = next;
+        state.elapsed.reset();
+        mode_changed.send(MovementModeChanged {
+            entity,
+            from,
+            to: next,
+        });
     }
+}
 
-    // Hold jump to extend height
-    if action_state.pressed(ActionKind::Jump) && !grounded && jump_extender.elapsed_secs() < 0.2 {
-        vel.y += 4.;
+fn jump(
+    mut player_query: Query<
+        (
+            &MovementInput,
+            &MovementState,
+            &mut LinearVelocity,
+            &mut InputBuffer,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
+    >,
+    time: Res<FixedTime>,
+    config: Res<MovementConfig>,
+) {
+    for (action_state, movement_state, mut vel, mut buffer, mut state) in player_query.iter_mut() {
+        let MovementState {
+            grounded,
+            on_platform,
+            falling,
+            ..
+        } = *movement_state;
+
+        let mode = state.mode();
+        if !mode.is_free() {
+            continue;
+        }
+
+        // Walked off a ledge shortly before
+        let can_coyote = mode == MovementMode::Airborne
+            && state.previous() == MovementMode::Grounded
+            && state.time_in_mode() < config.coyote_time
+            && falling;
+        // Down + Jump on a platform drops through it instead
+        let dropping = on_platform && action_state.pressed(ActionKind::Down);
+        // No room to jump while crouching
+        let crouching = mode == MovementMode::Crouching;
+        let can_jump = (grounded || can_coyote) && !dropping && !crouching && !state.is_changing();
+
+        // Jumps pressed shortly before landing still go through
+        if buffer.buffered(ActionKind::Jump, config.jump_buffer_time) && can_jump {
+            buffer.consume(ActionKind::Jump);
+            vel.y = config.jump_impulse;
+            state.set(MovementMode::Jumping);
+        }
+
+        // Hold jump to extend height
+        if action_state.pressed(ActionKind::Jump)
+            && mode == MovementMode::Jumping
+            && state.time_in_mode() < config.jump_extension_time
+        {
+            vel.y += config.jump_extension * time.period.as_secs_f32();
+        }
     }
 }
 
 fn wall_jump(
-    action_state_query: Query<&ActionState<ActionKind>>,
     mut player_query: Query<
-        (&mut LinearVelocity, &LookDir),
-        (With<Controllable>, Without<PoleClimb>),
+        (
+            &MovementInput,
+            &MovementState,
+            &mut LinearVelocity,
+            &LookDir,
+            &mut InputBuffer,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
     >,
-    time: Res<Time>,
-    movement_state: Res<MovementState>,
-    mut disabled_inputs: ResMut<DisabledInputs>,
+    config: Res<MovementConfig>,
 ) {
-    let Ok(action_state) = action_state_query.get_single() else {
-        return;
-    };
+    for (action_state, movement_state, mut vel, look_dir, mut buffer, mut state) in
+        player_query.iter_mut()
+    {
+        if !state.mode().is_free() || state.is(MovementMode::Grounded) || state.is_changing() {
+            continue;
+        }
 
-    let Ok((mut vel, look_dir)) = player_query.get_single_mut() else {
-        return;
-    };
+        let MovementState {
+            facing_wall: near_wall,
+            ..
+        } = *movement_state;
 
-    for disabled_input in disabled_inputs.iter_mut() {
-        disabled_input.1.tick(time.delta());
+        if buffer.buffered(ActionKind::Jump, config.jump_buffer_time) {
+            let press_in_look_dir = action_state.pressed(look_dir.as_action_kind());
+            if press_in_look_dir && near_wall {
+                buffer.consume(ActionKind::Jump);
+                vel.y = config.wall_jump_height;
+                **vel += look_dir.opposite().as_vec() * config.wall_jump_push;
+                state.set(MovementMode::WallJump(*look_dir));
+            }
+        }
     }
+}
 
-    let MovementState {
-        facing_wall: near_wall,
-        ..
-    } = *movement_state;
+fn drop_through(
+    mut cmds: Commands,
+    mut player_query: Query<
+        (
+            Entity,
+            &MovementInput,
+            &MovementState,
+            &mut InputBuffer,
+            &PlayerState,
+        ),
+        (With<Controllable>, Without<DropThrough>),
+    >,
+    config: Res<MovementConfig>,
+) {
+    for (player, action_state, movement_state, mut buffer, state) in player_query.iter_mut() {
+        if !state.mode().is_free() || state.is_changing() {
+            continue;
+        }
 
-    if action_state.just_pressed(ActionKind::Jump) {
-        let press_in_look_dir = action_state.pressed(look_dir.as_action_kind());
-        if press_in_look_dir && near_wall {
-            vel.y = 128.;
-            **vel += look_dir.opposite().as_vec() * 192.;
-            disabled_inputs.insert(
-                look_dir.as_action_kind(),
-                Timer::new(Duration::from_secs_f32(0.5), TimerMode::Once),
-            );
+        if movement_state.on_platform
+            && action_state.pressed(ActionKind::Down)
+            && buffer.buffered(ActionKind::Jump, config.jump_buffer_time)
+        {
+            buffer.consume(ActionKind::Jump);
+            cmds.entity(player).insert(DropThrough::default());
         }
     }
 }
 
-#[derive(Default, Component, Reflect)]
-#[reflect(Component)]
-pub struct PoleClimb(pub PoleType);
+// Pole closest to the given position among the overlapping ones
+fn closest_pole(
+    pos: Vector,
+    colliding: &CollidingEntities,
+    poles: &Query<(&Pole, &Position), Without<Controllable>>,
+) -> Option<(PoleType, Vector)> {
+    colliding
+        .0
+        .iter()
+        .filter_map(|other| poles.get(*other).ok())
+        .map(|(pole, pole_pos)| (pole.0, pole_pos.0))
+        .min_by(|(_, a), (_, b)| a.distance_squared(pos).total_cmp(&b.distance_squared(pos)))
+}
 
 fn pole_climb(
-    mut cmds: Commands,
-    poles: Query<&Pole>,
+    poles: Query<(&Pole, &Position), Without<Controllable>>,
     mut player: Query<
-        (Entity, &CollidingEntities, Option<&mut PoleClimb>),
-        (With<Controllable>, With<Player>),
+        (
+            &MovementInput,
+            &Position,
+            &CollidingEntities,
+            &mut InputBuffer,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
     >,
-    action_state_query: Query<&ActionState<ActionKind>>,
+    config: Res<MovementConfig>,
 ) {
-    let Ok(action_state) = action_state_query.get_single() else {
-        return;
-    };
-    let Ok((player, colliding, climb)) = player.get_single_mut() else {
-        return;
-    };
+    for (action_state, pos, colliding, mut buffer, mut state) in player.iter_mut() {
+        if matches!(
+            state.mode(),
+            MovementMode::Held | MovementMode::LedgeHang(_)
+        ) || state.is_changing()
+        {
+            continue;
+        }
 
-    let mut pole = None;
-    colliding.0.iter().for_each(|other| {
-        if let Ok(p) = poles.get(*other) {
-            match pole {
-                None => pole = Some(p),
-                Some(_) => match p.0 {
-                    PoleType::Combined => pole = Some(p),
-                    _ => {}
-                },
-            };
+        let climbing = matches!(state.mode(), MovementMode::PoleClimb(_));
+        let Some((pole, _)) = closest_pole(**pos, colliding, &poles) else {
+            if climbing {
+                state.set(MovementMode::Airborne);
+            }
+            continue;
         };
-    });
 
-    if let Some(pole) = pole {
-        if let Some(mut climb) = climb {
-            climb.0 = pole.0;
-        } else {
-            if action_state.pressed(ActionKind::Up) {
-                cmds.entity(player).insert(PoleClimb(pole.0));
+        if climbing {
+            // Moving onto another kind of pole, e.g. through a junction
+            if !state.is(MovementMode::PoleClimb(pole)) {
+                state.set(MovementMode::PoleClimb(pole));
             }
+            continue;
+        }
+
+        // Don't grab the pole that was just jumped off of
+        let just_left = matches!(state.previous(), MovementMode::PoleClimb(_))
+            && state.time_in_mode() < config.pole_regrab_time;
+
+        // Holding up or having pressed it right before reaching the pole grabs it
+        if !just_left
+            && (action_state.pressed(ActionKind::Up)
+                || buffer.buffered(ActionKind::Up, config.climb_buffer_time))
+        {
+            // Grabbing takes a jump pressed along with it, or it would throw the player off again
+            buffer.consume(ActionKind::Up);
+            buffer.consume(ActionKind::Jump);
+            state.set(MovementMode::PoleClimb(pole));
         }
-    } else {
-        cmds.entity(player).remove::<PoleClimb>();
     }
 }
 
 fn pole_movement(
-    mut cmds: Commands,
+    poles: Query<(&Pole, &Position), Without<Controllable>>,
+    platforms: Query<&ColliderAabb, With<Platform>>,
     mut player: Query<
-        (Entity, &mut LinearVelocity, &mut GravityScale, &PoleClimb),
-        (With<Controllable>, With<Player>),
+        (
+            Entity,
+            &MovementInput,
+            &mut Position,
+            &mut LinearVelocity,
+            &CollidingEntities,
+            &ColliderAabb,
+            &mut InputBuffer,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
     >,
-    action_state_query: Query<&ActionState<ActionKind>>,
+    spatial_query: SpatialQuery,
+    config: Res<MovementConfig>,
 ) {
-    let Ok(action_state) = action_state_query.get_single() else {
-        return;
-    };
+    for (player, action_state, mut pos, mut vel, colliding, collider_aabb, mut buffer, mut state) in
+        player.iter_mut()
+    {
+        let MovementMode::PoleClimb(_) = state.mode() else {
+            continue;
+        };
+        let Some((pole, pole_pos)) = closest_pole(**pos, colliding, &poles) else {
+            continue;
+        };
 
-    let Ok((player, mut vel, mut gravity, climb)) = player.get_single_mut() else {
-        return;
-    };
-    gravity.0 = 0.;
+        let axis = |negative: ActionKind, positive: ActionKind| {
+            action_state.pressed(positive) as i8 as Scalar
+                - action_state.pressed(negative) as i8 as Scalar
+        };
+        let vertical = axis(ActionKind::Down, ActionKind::Up);
+        let horizontal = axis(ActionKind::Left, ActionKind::Right);
 
-    match climb.0 {
-        PoleType::Vertical => {
-            vel.0 = Vec2::ZERO;
-            if action_state.pressed(ActionKind::Up) {
-                vel.y = 64.;
-            }
+        if buffer.buffered(ActionKind::Jump, config.jump_buffer_time) {
+            buffer.consume(ActionKind::Jump);
+            // Holding down just lets go
+            vel.0 = if vertical < 0. {
+                Vector::ZERO
+            } else {
+                Vector::new(horizontal * config.pole_jump_push, config.pole_jump_height)
+            };
+            state.set(MovementMode::Airborne);
+            continue;
+        }
 
-            if action_state.pressed(ActionKind::Down) {
-                vel.y = -64.;
-            }
+        // Junctions allow moving along either axis, one at a time
+        let along_vertical = match pole {
+            PoleType::Vertical => true,
+            PoleType::Horizontal => false,
+            PoleType::Combined => vertical != 0. || horizontal == 0.,
+        };
+
+        // Stay on the centre line of the pole
+        vel.0 = Vector::ZERO;
+        if along_vertical {
+            pos.x = pole_pos.x;
+            vel.y = vertical * config.pole_speed;
+        } else {
+            pos.y = pole_pos.y;
+            vel.x = horizontal * config.pole_speed;
+        }
+        if pole == PoleType::Combined && vertical == 0. && horizontal == 0. {
+            pos.0 = pole_pos;
         }
-        PoleType::Horizontal => {
-            vel.0 = Vec2::ZERO;
-            if action_state.pressed(ActionKind::Left) {
-                vel.x = -64.;
-            }
 
-            if action_state.pressed(ActionKind::Right) {
-                vel.x = 64.;
-            }
+        if !along_vertical || vertical <= 0. || pos.y < pole_pos.y {
+            continue;
         }
-        PoleType::Combined => {
-            vel.0 = Vec2::ZERO;
-            if action_state.pressed(ActionKind::Up) {
-                vel.y = 64.;
-            }
 
-            if action_state.pressed(ActionKind::Down) {
-                vel.y = -64.;
-            }
-            if action_state.pressed(ActionKind::Left) {
-                vel.x = -64.;
-            }
+        // Reached the top of the pole
+        let pole_above = colliding
+            .0
+            .iter()
+            .filter_map(|other| poles.get(*other).ok())
+            .any(|(_, other_pos)| other_pos.y > pole_pos.y + 1.);
+        if pole_above {
+            continue;
+        }
+        vel.y = 0.;
+        pos.y = pole_pos.y;
+
+        // Climb onto a platform right above the pole
+        let half_extents = collider_aabb.half_extents();
+        let platform_top = spatial_query
+            .ray_hits(
+                **pos,
+                Vector::Y,
+                half_extents.y + 12.,
+                8,
+                true,
+                SpatialQueryFilter::new().without_entities([player]),
+            )
+            .iter()
+            .filter_map(|hit| platforms.get(hit.entity).ok())
+            .map(|aabb| aabb.maxs.y)
+            .reduce(Scalar::min);
+        if let Some(top) = platform_top {
+            pos.y = top + half_extents.y + 0.5;
+            state.set(MovementMode::Airborne);
+        }
+    }
+}
+
+fn ledge_grab(
+    mut player_query: Query<
+        (
+            &MovementInput,
+            &MovementState,
+            &mut Position,
+            &mut LinearVelocity,
+            &ColliderAabb,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
+    >,
+    config: Res<MovementConfig>,
+) {
+    for (action_state, movement_state, mut pos, mut vel, collider_aabb, mut state) in
+        player_query.iter_mut()
+    {
+        // Some other transition, e.g. a wall jump, already happened this tick
+        if !state.mode().is_free() || state.is_changing() || movement_state.grounded {
+            continue;
+        }
+        let Some(ledge) = movement_state.ledge else {
+            continue;
+        };
+        if vel.y > 0. || !action_state.pressed(ledge.side.as_action_kind()) {
+            continue;
+        }
+        if state.previous() == MovementMode::LedgeHang(ledge.side)
+            && state.time_in_mode() < config.ledge_regrab_time
+        {
+            continue;
+        }
+
+        // Hang with the top of the player level with the ledge
+        let half_extents = collider_aabb.half_extents();
+        pos.x = ledge.corner.x - ledge.side.as_vec().x * half_extents.x;
+        pos.y = ledge.corner.y - half_extents.y;
+        vel.0 = Vector::ZERO;
+        state.set(MovementMode::LedgeHang(ledge.side));
+    }
+}
+
+fn ledge_climb(
+    mut player_query: Query<
+        (
+            Entity,
+            &MovementInput,
+            &MovementState,
+            &mut Position,
+            &mut LinearVelocity,
+            &Collider,
+            &ColliderAabb,
+            &mut InputBuffer,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
+    >,
+    q_terrain: Query<(), With<Terrain>>,
+    spatial_query: SpatialQuery,
+    config: Res<MovementConfig>,
+) {
+    for (
+        player,
+        action_state,
+        movement_state,
+        mut pos,
+        mut vel,
+        collider,
+        collider_aabb,
+        mut buffer,
+        mut state,
+    ) in player_query.iter_mut()
+    {
+        let MovementMode::LedgeHang(side) = state.mode() else {
+            continue;
+        };
+        vel.0 = Vector::ZERO;
+
+        // Ledge is gone, e.g. the tile got removed
+        let Some(ledge) = movement_state.ledge.filter(|ledge| ledge.side == side) else {
+            state.set(MovementMode::Airborne);
+            continue;
+        };
+
+        let away = side.opposite().as_action_kind();
+        if action_state.pressed(ActionKind::Down) || action_state.pressed(away) {
+            state.set(MovementMode::Airborne);
+            continue;
+        }
+
+        let climb = action_state.pressed(ActionKind::Up)
+            || buffer.buffered(ActionKind::Jump, config.jump_buffer_time);
+        if !climb {
+            continue;
+        }
+
+        // Stand on top of the tile the ledge belongs to, if there is room for it
+        let half_extents = collider_aabb.half_extents();
+        let target = ledge.corner
+            + Vector::new(
+                side.as_vec().x * (half_extents.x + 1.),
+                half_extents.y + 0.5,
+            );
+        let blocked = spatial_query
+            .shape_intersections(
+                collider,
+                target,
+                0.,
+                SpatialQueryFilter::new().without_entities([player]),
+            )
+            .into_iter()
+            .any(|entity| q_terrain.contains(entity));
+        if !blocked {
+            buffer.consume(ActionKind::Jump);
+            pos.0 = target;
+            state.set(MovementMode::Airborne);
+        }
+    }
+}
 
-            if action_state.pressed(ActionKind::Right) {
-                vel.x = 64.;
+fn crouch(
+    mut player_query: Query<
+        (
+            Entity,
+            &MovementInput,
+            &MovementState,
+            &Position,
+            &CrouchShape,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
+    >,
+    q_terrain: Query<(), With<Terrain>>,
+    spatial_query: SpatialQuery,
+) {
+    for (entity, action_state, movement_state, pos, shape, mut state) in player_query.iter_mut() {
+        if state.is_changing() {
+            continue;
+        }
+        let down = action_state.pressed(ActionKind::Down);
+        match state.mode() {
+            MovementMode::Grounded if down => state.set(MovementMode::Crouching),
+            MovementMode::Crouching if !down || !movement_state.grounded => {
+                // Stay down until there is enough room above
+                if shape.can_stand(entity, **pos, &spatial_query, &q_terrain) {
+                    state.set(if movement_state.grounded {
+                        MovementMode::Grounded
+                    } else {
+                        MovementMode::Airborne
+                    });
+                }
             }
+            _ => {}
         }
     }
+}
 
-    if action_state.just_pressed(ActionKind::Jump) {
-        cmds.entity(player).remove::<PoleClimb>();
+fn swim(
+    mut player_query: Query<
+        (
+            &MovementInput,
+            &mut LinearVelocity,
+            &mut LookDir,
+            &mut InputBuffer,
+            &mut PlayerState,
+        ),
+        With<Controllable>,
+    >,
+    config: Res<MovementConfig>,
+    time: Res<FixedTime>,
+) {
+    let dt = time.period.as_secs_f32();
+
+    for (action_state, mut vel, mut look_dir, mut buffer, mut state) in player_query.iter_mut() {
+        let mode = state.mode();
+        if !matches!(mode, MovementMode::Swimming | MovementMode::SwimDash) {
+            continue;
+        }
+
+        let axis = |negative: ActionKind, positive: ActionKind| {
+            action_state.pressed(positive) as i8 as Scalar
+                - action_state.pressed(negative) as i8 as Scalar
+        };
+        let dir = Vector::new(
+            axis(ActionKind::Left, ActionKind::Right),
+            axis(ActionKind::Down, ActionKind::Up),
+        )
+        .normalize_or_zero();
+        if dir.x < 0. {
+            *look_dir = LookDir::Left;
+        } else if dir.x > 0. {
+            *look_dir = LookDir::Right;
+        }
+
+        // Dashes keep their speed until they run out
+        if mode == MovementMode::SwimDash {
+            continue;
+        }
+
+        if buffer.buffered(ActionKind::Jump, config.jump_buffer_time)
+            && state.time_in_mode() >= config.swim_dash_cooldown
+            && !state.is_changing()
+        {
+            buffer.consume(ActionKind::Jump);
+            let dash_dir = if dir == Vector::ZERO {
+                look_dir.as_vec()
+            } else {
+                dir
+            };
+            vel.0 = dash_dir * config.swim_dash_speed;
+            state.set(MovementMode::SwimDash);
+            continue;
+        }
+
+        if dir == Vector::ZERO {
+            vel.0 *= (1. - config.swim_drag * dt).max(0.);
+        } else {
+            vel.0 += dir * config.swim_acceleration * dt;
+        }
+        vel.0 = vel.0.clamp_length_max(config.swim_speed);
     }
 }
 
-fn pole_gravity(mut climb: RemovedComponents<PoleClimb>, mut gravity: Query<&mut GravityScale>) {
-    for e in climb.iter() {
-        if let Some(mut gravity) = gravity.get_mut(e).ok() {
-            gravity.0 = 1.0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys::TIMESTEP;
+
+    // Grounded player pressing Up and Jump on the same tick, optionally next to a pole
+    fn up_and_jump(touching_pole: bool) -> (World, Entity) {
+        let mut world = World::new();
+        world.insert_resource(MovementConfig::default());
+        world.insert_resource(FixedTime::new_from_secs(TIMESTEP));
+
+        let pole = world
+            .spawn((Pole(PoleType::Vertical), Position::default()))
+            .id();
+        let mut colliding = CollidingEntities::default();
+        if touching_pole {
+            colliding.0.insert(pole);
+        }
+
+        let mut input = MovementInput::default();
+        let mut buffer = InputBuffer::default();
+        for action in [ActionKind::Up, ActionKind::Jump] {
+            input.set(action, true);
+            buffer.press(action);
         }
+        let state = PlayerState {
+            mode: MovementMode::Grounded,
+            ..default()
+        };
+        let movement_state = MovementState {
+            grounded: true,
+            ..default()
+        };
+
+        let player = world
+            .spawn((
+                Controllable,
+                input,
+                buffer,
+                state,
+                movement_state,
+                Position::default(),
+                LinearVelocity::default(),
+                colliding,
+            ))
+            .id();
+        (world, player)
+    }
+
+    fn run_transitions(world: &mut World) {
+        // Same priority as in the movement plugin
+        let mut schedule = Schedule::default();
+        schedule.add_systems((pole_climb, drop_through, jump).chain());
+        schedule.run(world);
+    }
+
+    #[test]
+    fn up_and_jump_beside_pole_grabs_it() {
+        let (mut world, player) = up_and_jump(true);
+        run_transitions(&mut world);
+
+        let state = world.get::<PlayerState>(player).unwrap();
+        assert_eq!(
+            state.next,
+            Some(MovementMode::PoleClimb(PoleType::Vertical))
+        );
+        assert_eq!(world.get::<LinearVelocity>(player).unwrap().y, 0.);
+        // The jump went into grabbing and doesn't throw the player off the pole next tick
+        let buffer = world.get::<InputBuffer>(player).unwrap();
+        assert!(!buffer.buffered(ActionKind::Jump, f32::MAX));
+    }
+
+    #[test]
+    fn up_and_jump_without_pole_jumps() {
+        let (mut world, player) = up_and_jump(false);
+        run_transitions(&mut world);
+
+        let state = world.get::<PlayerState>(player).unwrap();
+        assert_eq!(state.next, Some(MovementMode::Jumping));
+        assert!(world.get::<LinearVelocity>(player).unwrap().y > 0.);
     }
 }
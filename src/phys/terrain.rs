@@ -1,5 +1,6 @@
 use bevy::{prelude::*, utils::hashbrown::HashSet};
 use bevy_xpbd_2d::{math::*, prelude::*};
+use serde::{Deserialize, Serialize};
 
 #[derive(Component)]
 pub struct Terrain;
@@ -8,7 +9,7 @@ pub struct Terrain;
 #[reflect(Component)]
 pub struct Pole(pub PoleType);
 
-#[derive(Default, Clone, Copy, Reflect)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize)]
 pub enum PoleType {
     #[default]
     Horizontal,
@@ -22,9 +23,41 @@ pub struct Platform(pub HashSet<Entity>);
 #[derive(Component)]
 pub struct PlatformPass;
 
+// Entities with this component fall through platforms they are standing on until the timer finishes
+#[derive(Component)]
+pub struct DropThrough(pub Timer);
+
+impl DropThrough {
+    pub fn new(duration: f32) -> Self {
+        Self(Timer::from_seconds(duration, TimerMode::Once))
+    }
+}
+
+impl Default for DropThrough {
+    fn default() -> Self {
+        Self::new(0.25)
+    }
+}
+
+pub fn tick_drop_through(
+    mut cmds: Commands,
+    mut droppers: Query<(Entity, &mut DropThrough)>,
+    time: Res<FixedTime>,
+) {
+    for (entity, mut drop_through) in droppers.iter_mut() {
+        drop_through.0.tick(time.period);
+        if drop_through.0.finished() {
+            cmds.entity(entity).remove::<DropThrough>();
+        }
+    }
+}
+
 pub fn handle_platforms(
     mut platforms: Query<&mut Platform>,
-    passers: Query<Option<&PlatformPass>, (With<Collider>, Without<Platform>)>,
+    passers: Query<
+        (Option<&PlatformPass>, Option<&DropThrough>),
+        (With<Collider>, Without<Platform>),
+    >,
     mut collisions: ResMut<Collisions>,
 ) {
     collisions.retain(|contacts| {
@@ -60,15 +93,21 @@ pub fn handle_platforms(
         }
 
         match passers.get(other) {
-            Ok(_) => {
-                if contacts.manifolds.iter().all(|manifold| {
+            Ok((_, drop_through)) => {
+                let from_above = contacts.manifolds.iter().all(|manifold| {
                     let normal = match normal {
                         RelevantNormal::Normal1 => manifold.normal1,
                         RelevantNormal::Normal2 => manifold.normal2,
                     };
 
                     normal.length() > Scalar::EPSILON && normal.dot(Vector::Y) >= 0.5
-                }) {
+                });
+
+                if from_above && drop_through.is_some() {
+                    // Let it fall through until it stops touching the platform
+                    platform.0.insert(other);
+                    false
+                } else if from_above {
                     true
                 } else if any_penetrating(&contacts) {
                     platform.0.insert(other);
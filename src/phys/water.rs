@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use bevy_xpbd_2d::{math::*, parry::bounding_volume::Aabb, prelude::*};
+
+use super::terrain::Terrain;
+
+// How quickly bodies in water slow down, per second
+pub const WATER_DRAG: Scalar = 3.;
+
+// Marks a water volume, bodies overlapping it are submerged
+#[derive(Component, Default)]
+pub struct Water;
+
+// Density relative to water, bodies with less than 1 float and bodies with more sink
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct Density(pub Scalar);
+
+impl Default for Density {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+// Fraction of the body that is under water
+#[derive(Component, Default, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct Submerged(pub Scalar);
+
+pub fn update_submerged(
+    mut cmds: Commands,
+    bodies: Query<
+        (
+            Entity,
+            &ColliderAabb,
+            &CollidingEntities,
+            Option<&Submerged>,
+        ),
+        (Without<Water>, Without<Terrain>, Without<Sensor>),
+    >,
+    water: Query<&ColliderAabb, With<Water>>,
+) {
+    let area = |aabb: &Aabb| {
+        let half_extents = aabb.half_extents();
+        half_extents.x * half_extents.y * 4.
+    };
+
+    for (entity, aabb, colliding, submerged) in bodies.iter() {
+        // Water tiles don't overlap, so their intersections can simply be added up
+        let covered: Scalar = colliding
+            .0
+            .iter()
+            .filter_map(|other| water.get(*other).ok())
+            .filter_map(|water_aabb| aabb.intersection(water_aabb))
+            .map(|intersection| area(&intersection))
+            .sum();
+        let fraction = (covered / area(aabb).max(Scalar::EPSILON)).min(1.);
+
+        match (fraction > 0., submerged) {
+            (true, _) => {
+                cmds.entity(entity).insert(Submerged(fraction));
+            }
+            (false, Some(_)) => {
+                cmds.entity(entity).remove::<Submerged>();
+            }
+            (false, None) => {}
+        }
+    }
+}
+
+// Pushes bodies up proportionally to how much of them is under water and slows them down
+pub fn apply_buoyancy(
+    mut bodies: Query<(
+        &Density,
+        &Submerged,
+        &mut LinearVelocity,
+        Option<&GravityScale>,
+    )>,
+    gravity: Res<Gravity>,
+    time: Res<FixedTime>,
+) {
+    let dt = time.period.as_secs_f32();
+    for (density, submerged, mut vel, gravity_scale) in bodies.iter_mut() {
+        let scale = gravity_scale.map_or(1., |scale| scale.0);
+        let buoyancy = -gravity.0 * scale * submerged.0 / density.0.max(Scalar::EPSILON);
+        vel.0 += buoyancy * dt;
+        vel.0 *= (1. - WATER_DRAG * submerged.0 * dt).max(0.);
+    }
+}
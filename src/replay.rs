@@ -0,0 +1,403 @@
+use std::{fs, path::PathBuf};
+
+use bevy::{
+    ecs::system::{Command, SystemState},
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContexts};
+use bevy_xpbd_2d::{math::Vector, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    editor::EditorState,
+    entity::player::{DespawnPlayerCommand, Player, SpawnPlayerCommand},
+    level::serialization::LevelSerializer,
+    phys::movement::{latch_virtual_input, CrouchShape, MovementInput, MovementSet},
+};
+
+pub const REPLAY_PATH: &str = "replays/latest.replay.ron";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                record_inputs.run_if(resource_exists::<ReplayRecorder>()),
+                play_inputs.run_if(resource_exists::<ReplayPlayback>()),
+            )
+                .after(latch_virtual_input)
+                .before(MovementSet),
+        );
+        app.add_systems(
+            Update,
+            (
+                replay_controls,
+                (draw_replay_controls, seek_replay)
+                    .chain()
+                    .run_if(resource_exists::<ReplayPlayback>()),
+            ),
+        );
+    }
+}
+
+// Everything needed to reproduce a run, assuming the simulation is deterministic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    // Level file the run was recorded on, replays on the current map if there is none
+    pub level: Option<PathBuf>,
+    pub start: Vector,
+    pub size: Vector,
+    // Player input of every fixed tick
+    pub ticks: Vec<MovementInput>,
+}
+
+impl Replay {
+    pub fn save_to_file(&self, path: PathBuf) {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap();
+        if let Err(err) = fs::write(path, ron.as_bytes()) {
+            warn!("Failed to save replay: {}", err);
+        }
+    }
+
+    pub fn load_from_file(path: PathBuf) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        ron::from_str(&data).ok()
+    }
+}
+
+// Exists while recording
+#[derive(Resource)]
+pub struct ReplayRecorder(pub Replay);
+
+// Exists while playing back a replay
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    // Index of the next tick to play
+    pub tick: usize,
+    pub paused: bool,
+    pub seek: Option<usize>,
+}
+
+// Puts the level and player back to how they were when the replay started
+struct RestartReplayCommand;
+
+impl Command for RestartReplayCommand {
+    fn apply(self, world: &mut World) {
+        let Some(playback) = world.get_resource::<ReplayPlayback>() else {
+            return;
+        };
+        let replay = playback.replay.clone();
+
+        if let Some(level) = replay.level {
+            let mut state = SystemState::<LevelSerializer>::new(world);
+            state.get_mut(world).load_from_file(level);
+            state.apply(world);
+        }
+
+        DespawnPlayerCommand.apply(world);
+        SpawnPlayerCommand::new(replay.start, replay.size, ()).apply(world);
+        world.resource_mut::<ReplayPlayback>().tick = 0;
+    }
+}
+
+// Respawns the player so the recording starts from a clean state
+pub struct StartRecordingCommand {
+    pub level: Option<PathBuf>,
+}
+
+impl Command for StartRecordingCommand {
+    fn apply(self, world: &mut World) {
+        let mut player = world.query_filtered::<(&Position, &CrouchShape), With<Player>>();
+        let Ok((pos, shape)) = player.get_single(world) else {
+            warn!("Can't record a replay without a player");
+            return;
+        };
+        let (start, size) = (pos.0, shape.standing);
+
+        DespawnPlayerCommand.apply(world);
+        SpawnPlayerCommand::new(start, size, ()).apply(world);
+        world.insert_resource(ReplayRecorder(Replay {
+            level: self.level,
+            start,
+            size,
+            ticks: Vec::new(),
+        }));
+    }
+}
+
+pub struct StopRecordingCommand {
+    pub path: PathBuf,
+}
+
+impl Command for StopRecordingCommand {
+    fn apply(self, world: &mut World) {
+        if let Some(recorder) = world.remove_resource::<ReplayRecorder>() {
+            info!("Saved replay of {} ticks", recorder.0.ticks.len());
+            recorder.0.save_to_file(self.path);
+        }
+    }
+}
+
+pub struct StartPlaybackCommand {
+    pub path: PathBuf,
+}
+
+impl Command for StartPlaybackCommand {
+    fn apply(self, world: &mut World) {
+        let Some(replay) = Replay::load_from_file(self.path) else {
+            warn!("Failed to load replay");
+            return;
+        };
+        world.insert_resource(ReplayPlayback {
+            replay,
+            tick: 0,
+            paused: false,
+            seek: None,
+        });
+        world.resource_mut::<Time>().unpause();
+        RestartReplayCommand.apply(world);
+    }
+}
+
+pub struct StopPlaybackCommand;
+
+impl Command for StopPlaybackCommand {
+    fn apply(self, world: &mut World) {
+        world.remove_resource::<ReplayPlayback>();
+        world.resource_mut::<Time>().unpause();
+    }
+}
+
+fn record_inputs(
+    mut recorder: ResMut<ReplayRecorder>,
+    player: Query<&MovementInput, With<Player>>,
+) {
+    let Ok(input) = player.get_single() else {
+        return;
+    };
+    recorder.0.ticks.push(*input);
+}
+
+// Replaces whatever the keyboard latched with the recorded input
+fn play_inputs(
+    mut playback: ResMut<ReplayPlayback>,
+    mut player: Query<&mut MovementInput, With<Player>>,
+) {
+    let Some(recorded) = playback.replay.ticks.get(playback.tick).copied() else {
+        return;
+    };
+    let Ok(mut input) = player.get_single_mut() else {
+        return;
+    };
+    *input = recorded;
+    playback.tick += 1;
+}
+
+fn replay_controls(
+    mut cmds: Commands,
+    keys: Res<Input<KeyCode>>,
+    recorder: Option<Res<ReplayRecorder>>,
+    playback: Option<Res<ReplayPlayback>>,
+    editor_state: Option<Res<EditorState>>,
+) {
+    if keys.just_pressed(KeyCode::F9) && playback.is_none() {
+        if recorder.is_some() {
+            cmds.add(StopRecordingCommand {
+                path: REPLAY_PATH.into(),
+            });
+        } else {
+            cmds.add(StartRecordingCommand {
+                level: editor_state.and_then(|state| state.current_loaded_path.clone()),
+            });
+        }
+    }
+    if keys.just_pressed(KeyCode::F10) && recorder.is_none() {
+        if playback.is_some() {
+            cmds.add(StopPlaybackCommand);
+        } else {
+            cmds.add(StartPlaybackCommand {
+                path: REPLAY_PATH.into(),
+            });
+        }
+    }
+}
+
+fn draw_replay_controls(
+    mut cmds: Commands,
+    mut contexts: EguiContexts,
+    mut playback: ResMut<ReplayPlayback>,
+) {
+    let len = playback.replay.ticks.len();
+    let mut target = playback.seek.unwrap_or(playback.tick);
+
+    egui::Window::new("Replay")
+        .resizable(false)
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                let label = if playback.paused { "Play" } else { "Pause" };
+                if ui.button(label).clicked() {
+                    playback.paused = !playback.paused;
+                }
+                if ui.button("Stop").clicked() {
+                    cmds.add(StopPlaybackCommand);
+                }
+                ui.label(format!("{} / {}", playback.tick, len));
+            });
+            let slider = ui.add(egui::Slider::new(&mut target, 0..=len).show_value(false));
+            if slider.changed() {
+                playback.seek = Some(target);
+            }
+        });
+}
+
+fn seek_replay(
+    mut cmds: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    mut time: ResMut<Time>,
+    mut fixed_time: ResMut<FixedTime>,
+) {
+    if let Some(target) = playback.seek.take() {
+        // Ticks can only be simulated forwards, going back means starting over
+        let skip = if target < playback.tick {
+            cmds.add(RestartReplayCommand);
+            target
+        } else {
+            target - playback.tick
+        };
+        // Runs all skipped ticks during the next fixed update
+        let period = fixed_time.period;
+        fixed_time.tick(period * skip as u32);
+    }
+
+    let finished = playback.tick >= playback.replay.ticks.len();
+    let paused = playback.paused || finished;
+    if paused != time.is_paused() {
+        if paused {
+            time.pause();
+        } else {
+            time.unpause();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use leafwing_input_manager::Actionlike;
+
+    use crate::{
+        entity::{
+            holdable::{carry_held, pick_up, throw, CanHold, Holdable, Holding, IsHeld},
+            projectile::Thrown,
+        },
+        phys::{
+            movement::{consume_movement_input, ActionKind, LookDir},
+            TIMESTEP,
+        },
+    };
+
+    use super::*;
+
+    // Inputs of consecutive ticks with the given actions held down
+    fn record(ticks: &[&[ActionKind]]) -> Vec<MovementInput> {
+        let mut input = MovementInput::default();
+        ticks
+            .iter()
+            .map(|pressed| {
+                input.consume();
+                for action in ActionKind::variants() {
+                    input.set(action, pressed.contains(&action));
+                }
+                input
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plays_back_pick_up_and_throw() {
+        use ActionKind::*;
+
+        let mut app = App::new();
+        app.add_plugins(ReplayPlugin);
+        // Same ordering as in the physics plugin
+        app.add_systems(
+            FixedUpdate,
+            (
+                latch_virtual_input.before(MovementSet),
+                carry_held.after(MovementSet),
+                (pick_up, throw.after(carry_held))
+                    .after(MovementSet)
+                    .before(consume_movement_input),
+                consume_movement_input.after(MovementSet),
+            ),
+        );
+        app.insert_resource(FixedTime::new_from_secs(TIMESTEP));
+        app.insert_resource(ReplayPlayback {
+            replay: Replay {
+                level: None,
+                start: Vector::ZERO,
+                size: Vector::new(12., 24.),
+                ticks: record(&[&[PickUp], &[Throw], &[Throw], &[Throw], &[]]),
+            },
+            tick: 0,
+            paused: false,
+            seek: None,
+        });
+
+        let item = app
+            .world
+            .spawn((
+                Holdable::pebble(),
+                Position::default(),
+                Rotation::default(),
+                LinearVelocity::default(),
+            ))
+            .id();
+        let mut colliding = CollidingEntities::default();
+        colliding.0.insert(item);
+        let player = app
+            .world
+            .spawn((
+                Player,
+                CanHold,
+                MovementInput::default(),
+                colliding,
+                Position::default(),
+                LinearVelocity::default(),
+                ColliderAabb::from_shape(Collider::cuboid(12., 24.).get_shape()),
+                LookDir::Right,
+            ))
+            .id();
+
+        // Keys held live during playback must not change the outcome
+        let live = record(&[&[PickUp, Throw, Up]])[0];
+        let tick = |app: &mut App| {
+            *app.world.get_mut::<MovementInput>(player).unwrap() = live;
+            app.world.run_schedule(FixedUpdate);
+        };
+
+        tick(&mut app);
+        assert!(app.world.get::<Holding>(player).is_some());
+        assert!(app.world.get::<IsHeld>(item).is_some());
+
+        for _ in 0..3 {
+            tick(&mut app);
+            assert!(app.world.get::<Holding>(player).is_some());
+        }
+
+        tick(&mut app);
+        assert_eq!(app.world.resource::<ReplayPlayback>().tick, 5);
+        assert!(app.world.get::<Holding>(player).is_none());
+        assert!(app.world.get::<IsHeld>(item).is_none());
+        assert_eq!(app.world.get::<Thrown>(item).unwrap().thrower, player);
+        // Released without aiming up, so the throw goes forward and slightly up
+        let vel = app.world.get::<LinearVelocity>(item).unwrap().0;
+        assert!(vel.x > 0. && vel.y > 0. && vel.x > vel.y);
+    }
+}
@@ -12,3 +12,18 @@ pub fn box_lines(origin: Vec2, size: Vec2) -> [(Vec2, Vec2); 4] {
 
     [bottom_right, bottom_up, top_left, top_down]
 }
+
+// Whether the segments (start, end) cross each other
+pub fn segments_intersect(a: (Vec2, Vec2), b: (Vec2, Vec2)) -> bool {
+    let a_dir = a.1 - a.0;
+    let b_dir = b.1 - b.0;
+    let denom = a_dir.perp_dot(b_dir);
+    // Parallel segments never count as crossing
+    if denom.abs() < f32::EPSILON {
+        return false;
+    }
+    let offset = b.0 - a.0;
+    let t = offset.perp_dot(b_dir) / denom;
+    let u = offset.perp_dot(a_dir) / denom;
+    (0. ..=1.).contains(&t) && (0. ..=1.).contains(&u)
+}